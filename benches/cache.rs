@@ -0,0 +1,198 @@
+//! Compares the sharded [`Cache`] against the previous design,
+//! a `HashMap` with locked stats shared as an `Arc<RwLock<_>>`,
+//! under the access pattern of `WebScraper::get_many`: rayon workers look a key up
+//! and add it on a miss.
+//!
+//! Every cache is backed by a real file in a temp dir and flushes with the default
+//! [`FlushPolicy`](booth_archiver::zaphkiel::cache::FlushPolicy), every 100 writes,
+//! as in `archive`. The sharded cache is measured with both the ron and the redb backend.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use rayon::prelude::*;
+
+use booth_archiver::zaphkiel::backend::{meta_path, write_ron};
+use booth_archiver::zaphkiel::cache::{Cache, CacheStats, EntryMeta};
+
+const KEYS: usize = 10_000;
+const FLUSH_EVERY: u64 = 100;
+
+/// the cache as it was before it was sharded, every read takes the stats and log locks
+/// and every 100th add writes the whole cache while holding the write lock
+struct LockedCache {
+    cache: HashMap<String, String>,
+    meta: HashMap<String, EntryMeta>,
+    stats: Arc<RwLock<CacheStats>>,
+    misses: Arc<RwLock<Vec<String>>>,
+    hits: Arc<RwLock<Vec<String>>>,
+    path: PathBuf,
+    writes: u64,
+}
+
+impl LockedCache {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            cache: HashMap::new(),
+            meta: HashMap::new(),
+            stats: Arc::default(),
+            misses: Arc::default(),
+            hits: Arc::default(),
+            path,
+            writes: 0,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        let value = self.cache.get(key).map(|value| value.as_str().to_string());
+        if value.is_some() {
+            self.stats.write().unwrap().cache_hits += 1;
+            self.hits.write().unwrap().push(key.to_string());
+        } else {
+            self.stats.write().unwrap().cache_misses += 1;
+            self.misses.write().unwrap().push(key.to_string());
+        }
+        value
+    }
+
+    fn add(&mut self, key: String, value: String) {
+        self.meta.insert(key.clone(), EntryMeta::now());
+        self.cache.insert(key, value);
+        self.writes += 1;
+        if self.writes >= FLUSH_EVERY {
+            self.dump();
+        }
+    }
+
+    fn dump(&mut self) {
+        write_ron(&self.path, &self.cache);
+        write_ron(&meta_path(&self.path), &self.meta);
+        self.writes = 0;
+    }
+}
+
+fn keys() -> Vec<String> {
+    (0..KEYS)
+        .map(|i| format!("https://booth.pm/en/items/{i}.json"))
+        .collect()
+}
+
+/// an empty temp dir for one benchmark case
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bench-cache-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// a cache at `path` holding every `step`th key, with everything flushed
+fn filled_cache(path: &Path, keys: &[String], step: usize) -> Arc<Cache> {
+    let cache: Arc<Cache> = Arc::new(Cache::new_with_path(path.to_path_buf()));
+    cache.clear();
+    keys.iter()
+        .step_by(step)
+        .for_each(|key| cache.add(key.clone(), key.clone()));
+    cache.dump();
+    cache
+}
+
+/// every other key is cached already, the rest are misses that get added
+fn bench_get_or_add(c: &mut Criterion) {
+    let keys = keys();
+    let mut group = c.benchmark_group("get_or_add");
+    // every iteration rewrites the cache file dozens of times
+    group.sample_size(10);
+
+    let dir = temp_dir("rwlock");
+    group.bench_function(BenchmarkId::new("rwlock", KEYS), |b| {
+        b.iter_batched(
+            || {
+                let mut locked = LockedCache::new(dir.join("cache.ron"));
+                keys.iter()
+                    .step_by(2)
+                    .for_each(|key| locked.add(key.clone(), key.clone()));
+                locked.dump();
+                Arc::new(RwLock::new(locked))
+            },
+            |cache| {
+                keys.par_iter().for_each(|key| {
+                    let cached = cache.read().unwrap().get(key);
+                    if cached.is_none() {
+                        cache
+                            .write()
+                            .unwrap()
+                            .add(key.clone(), black_box(key.clone()));
+                    }
+                });
+                cache.write().unwrap().dump();
+            },
+            BatchSize::PerIteration,
+        );
+    });
+    fs::remove_dir_all(&dir).unwrap();
+
+    for format in ["ron", "redb"] {
+        let dir = temp_dir(format);
+        let path = dir.join(format!("cache.{format}"));
+        group.bench_function(BenchmarkId::new(format!("sharded-{format}"), KEYS), |b| {
+            b.iter_batched(
+                || filled_cache(&path, &keys, 2),
+                |cache| {
+                    keys.par_iter().for_each(|key| {
+                        if cache.get(key).is_none() {
+                            cache.add(key.clone(), black_box(key.clone()));
+                        }
+                    });
+                    cache.dump();
+                },
+                BatchSize::PerIteration,
+            );
+        });
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    group.finish();
+}
+
+/// everything is cached, as on a rerun of the archiver
+fn bench_all_hits(c: &mut Criterion) {
+    let keys = keys();
+    let mut group = c.benchmark_group("all_hits");
+
+    let dir = temp_dir("rwlock-hits");
+    let mut locked = LockedCache::new(dir.join("cache.ron"));
+    keys.iter()
+        .for_each(|key| locked.add(key.clone(), key.clone()));
+    locked.dump();
+    let locked = Arc::new(RwLock::new(locked));
+    group.bench_function(BenchmarkId::new("rwlock", KEYS), |b| {
+        b.iter(|| {
+            keys.par_iter().for_each(|key| {
+                black_box(locked.read().unwrap().get(key));
+            });
+        });
+    });
+    fs::remove_dir_all(&dir).unwrap();
+
+    for format in ["ron", "redb"] {
+        let dir = temp_dir(&format!("{format}-hits"));
+        let cache = filled_cache(&dir.join(format!("cache.{format}")), &keys, 1);
+        group.bench_function(BenchmarkId::new(format!("sharded-{format}"), KEYS), |b| {
+            b.iter(|| {
+                keys.par_iter().for_each(|key| {
+                    black_box(cache.get(key));
+                });
+            });
+        });
+        drop(cache);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_get_or_add, bench_all_hits);
+criterion_main!(benches);
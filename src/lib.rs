@@ -1,13 +1,28 @@
 pub mod models {
+    pub mod avatars;
+    pub mod bilingual;
     pub mod booth_scrapper;
+    pub mod config;
+    pub mod description;
+    pub mod glossary;
+    pub mod html;
     pub mod item_row;
+    pub mod language;
+    pub mod markdown;
+    pub mod overrides;
+    pub mod search;
+    pub mod segmenter;
+    pub mod thumbnails;
     pub mod translation;
+    pub mod translator;
     pub mod web_client;
     pub mod xlsx;
 }
 
 pub mod zaphkiel {
+    pub mod backend;
     pub mod cache;
+    pub mod codec;
     pub mod macros;
     pub mod utils;
 }
@@ -16,3 +31,6 @@ pub mod api_structs {
     pub mod items;
     pub mod wish_list_name_items;
 }
+
+#[cfg(test)]
+mod test_utils;
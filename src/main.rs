@@ -1,7 +1,9 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex, RwLock};
-use std::time::Instant;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use indicatif::ParallelProgressIterator;
 use rayon::prelude::*;
@@ -9,19 +11,513 @@ use rust_xlsxwriter::Workbook;
 
 use booth_archiver::api_structs::items::ItemApiResponse;
 use booth_archiver::api_structs::wish_list_name_items::WishListNameItemsResponse;
+use booth_archiver::models::avatars::AvatarMatcher;
 use booth_archiver::models::booth_scrapper::{
     get_all_item_numbers_on_page, get_all_wishlist_pages,
 };
+use booth_archiver::models::config::{CacheTtls, Config};
+use booth_archiver::models::glossary::Glossaries;
+use booth_archiver::models::html::write_html;
 use booth_archiver::models::item_row::ItemRow;
+use booth_archiver::models::markdown::write_markdown;
+use booth_archiver::models::overrides::Overrides;
+use booth_archiver::models::search::SearchIndex;
+use booth_archiver::models::segmenter::Segmenter;
+use booth_archiver::models::thumbnails::download_thumbnails;
+use booth_archiver::models::translator::{TranslationPipeline, TranslatorConfig};
 use booth_archiver::models::web_client::WebScraper;
-use booth_archiver::models::xlsx::{format_cols, save_book, write_all, write_headers};
-use booth_archiver::zaphkiel::cache::Cache;
-use booth_archiver::zaphkiel::utils::get_pb;
+use booth_archiver::models::xlsx::{
+    format_cols, insert_thumbnails, save_book, write_all, write_headers, write_shops_sheet,
+    write_summary_sheet, write_tags_sheet, write_variations_sheet, ITEMS_SHEET,
+};
+use booth_archiver::zaphkiel::backend::{
+    cache_size, is_cache_file, meta_path, migrate, CacheFormat,
+};
+use booth_archiver::zaphkiel::cache::{Cache, FlushPolicy};
+use booth_archiver::zaphkiel::codec::Json;
+use booth_archiver::zaphkiel::utils::{get_pb, glob_match, parse_duration};
 use booth_archiver::{debug, time_it, write_items_to_file};
 
+const USAGE: &str = "usage:
+    booth_archiver [archive] [--refresh-older-than <age, e.g. 30m, 12h, 7d>]
+    booth_archiver translations export [path]
+    booth_archiver translations import [path]
+    booth_archiver search <query>
+    booth_archiver cache list <cache> [glob]
+    booth_archiver cache show <cache> <key>
+    booth_archiver cache delete <cache> <key or glob>
+    booth_archiver cache prune
+    booth_archiver cache vacuum [cache]
+    booth_archiver cache export <cache> <path>    (with the metadata, e.g. x.meta.ron for x.ron)
+    booth_archiver cache import <cache> <path>    (replaces the cache, does not merge)
+    booth_archiver cache compress";
+
+/// The caches keyed by item urls, which `cache prune` cleans up.
+const ITEM_CACHES: [&str; 2] = ["all_items", "prices"];
+
+/// How many results `search` shows.
+const SEARCH_LIMIT: usize = 20;
+
+/// Where `archive` leaves the item rows for the other commands.
+const ITEM_ROWS_PATH: &str = "temp/item_rows.json";
+
+/// Where the caches live.
+const CACHE_DIR: &str = "cache";
+
+/// Where `translations export` writes the strings to translate by default.
+const PENDING_TRANSLATIONS_PATH: &str = "temp/pending_translations.ron";
+
+fn main() {
+    let config = Config::load("config.ron");
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let refresh_older_than = args
+        .iter()
+        .position(|arg| *arg == "--refresh-older-than")
+        .map(|idx| {
+            let age = args.get(idx + 1).copied().unwrap_or_default();
+            let age = parse_duration(age).unwrap_or_else(|| panic!("invalid age `{age}`\n{USAGE}"));
+            args.drain(idx..(idx + 2).min(args.len()));
+            age
+        });
+
+    match args.as_slice() {
+        [] | ["archive"] => archive(&config, refresh_older_than),
+        ["translations", "export", path @ ..] => {
+            export_translations(&config, path.first().unwrap_or(&PENDING_TRANSLATIONS_PATH));
+        }
+        ["translations", "import", path @ ..] => {
+            import_translations(&config, path.first().unwrap_or(&PENDING_TRANSLATIONS_PATH));
+        }
+        ["search", query @ ..] if !query.is_empty() => search(&query.join(" ")),
+        ["cache", args @ ..] => {
+            if let Err(e) = cache_command(&config, args) {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        _ => println!("{USAGE}"),
+    }
+}
+
+/// Run one of the `cache` commands.
+fn cache_command(config: &Config, args: &[&str]) -> Result<(), String> {
+    match args {
+        ["list", name, glob @ ..] => list_cache(config, name, glob.first().copied()),
+        ["show", name, key] => show_cache_entry(config, name, key),
+        ["delete", name, pattern] => delete_cache_entries(config, name, pattern),
+        ["prune"] => prune_caches(config),
+        ["vacuum", name @ ..] => vacuum_caches(config, name.first().copied()),
+        ["export", name, path] => {
+            let path = Path::new(path);
+            open_cache(config, name)?.dump_to_file(path);
+            println!(
+                "exported `{name}` to `{}` and `{}`",
+                path.display(),
+                meta_path(path).display()
+            );
+            Ok(())
+        }
+        ["import", name, path] => import_cache(config, name, Path::new(path)),
+        ["compress"] => {
+            compress_caches(config);
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+/// Read the item rows the last `archive` run left behind.
+fn load_item_rows() -> Vec<ItemRow> {
+    let item_rows = fs::read_to_string(ITEM_ROWS_PATH).unwrap_or_else(|e| {
+        panic!("failed to read `{ITEM_ROWS_PATH}` because of error: {e}, run `archive` first")
+    });
+
+    serde_json::from_str(&item_rows)
+        .unwrap_or_else(|e| panic!("failed to parse `{ITEM_ROWS_PATH}` because of error: {e}"))
+}
+
+/// Print the items best matching `query`.
+fn search(query: &str) {
+    let item_rows = load_item_rows();
+    let index = time_it!("building search index" => SearchIndex::build(&item_rows));
+
+    let item_rows_by_id = item_rows
+        .iter()
+        .map(|item_row| (item_row.id, item_row))
+        .collect::<HashMap<_, _>>();
+
+    for hit in index.search(query, SEARCH_LIMIT) {
+        let item_row = item_rows_by_id[&hit.id];
+        println!(
+            "{:>8.3} {} {}\n         {}\n         {}",
+            hit.score, hit.id, item_row.item_name, item_row.item_link, hit.snippet
+        );
+    }
+}
+
+/// Write the strings without an override or a trustworthy machine translation to `path`.
+fn export_translations(config: &Config, path: &str) {
+    let overrides = Overrides::load(&config.overrides_path);
+    let pending = overrides.pending(&load_item_rows());
+
+    ron::ser::to_writer_pretty(
+        fs::File::create(path).unwrap(),
+        &pending,
+        ron::ser::PrettyConfig::default(),
+    )
+    .unwrap();
+
+    println!(
+        "exported {} strings to translate to `{path}`",
+        pending.len()
+    );
+}
+
+/// Read the translated strings from `path` into the overrides.
+fn import_translations(config: &Config, path: &str) {
+    let pending = fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("failed to read `{path}` because of error: {e}"));
+    let pending = ron::from_str(&pending)
+        .unwrap_or_else(|e| panic!("failed to parse `{path}` because of error: {e}"));
+
+    let mut overrides = Overrides::load(&config.overrides_path);
+    let changed = overrides.import(pending);
+    overrides.save(&config.overrides_path);
+
+    println!(
+        "imported {changed} translations into `{}`",
+        config.overrides_path.display()
+    );
+}
+
+/// The cache files in `cache/`, in any format.
+fn cache_files() -> Vec<PathBuf> {
+    let entries = match fs::read_dir(CACHE_DIR) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return vec![],
+        Err(e) => panic!("failed to read `{CACHE_DIR}` because of error: {e}"),
+    };
+    let mut paths = entries
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| is_cache_file(path))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    paths
+}
+
+/// The name of the cache at `path`, e.g. `all_items` for `cache/all_items.ron.zst`.
+fn cache_name(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let extension = CacheFormat::from_path(path).extension();
+
+    name.strip_suffix(&format!(".{extension}"))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+/// Open the cache called `name`, e.g. `all_items`, with its configured ttl,
+/// changes are only saved by an explicit dump.
+///
+/// Caches in the configured format come first, caches that were not converted to it yet are found too.
+fn open_cache(config: &Config, name: &str) -> Result<Cache, String> {
+    let path = Some(config.cache_path(name))
+        .filter(|path| fs::metadata(path).is_ok())
+        .or_else(|| {
+            cache_files()
+                .into_iter()
+                .find(|path| cache_name(path) == name)
+        })
+        .ok_or_else(|| {
+            let names = cache_files()
+                .iter()
+                .map(|path| cache_name(path))
+                .collect::<Vec<_>>();
+            if names.is_empty() {
+                format!("there is no cache called `{name}`, there are no caches yet")
+            } else {
+                format!(
+                    "there is no cache called `{name}`, the caches are: {}",
+                    names.join(", ")
+                )
+            }
+        })?;
+
+    Ok(with_ttl(config, name, Cache::new_with_path(path)))
+}
+
+/// `cache` with the ttl configured for the cache called `name` and manual flushes.
+fn with_ttl(config: &Config, name: &str, cache: Cache) -> Cache {
+    cache
+        .with_ttl(config.cache_ttls.of(name).map(Duration::from_secs))
+        .with_flush_policy(FlushPolicy::manual())
+}
+
+/// Replace the cache called `name` with an export, creating it in the configured format if needed.
+fn import_cache(config: &Config, name: &str, path: &Path) -> Result<(), String> {
+    if fs::metadata(path).is_err() {
+        return Err(format!("there is no export at `{}`", path.display()));
+    }
+    fs::create_dir_all(CACHE_DIR)
+        .map_err(|e| format!("failed to create `{CACHE_DIR}` because of error: {e}"))?;
+
+    let cache = with_ttl(config, name, Cache::new_with_path(config.cache_path(name)));
+    cache.pump_from_file(path);
+    cache.dump();
+    println!(
+        "replaced `{name}` with the {} entries from `{}`",
+        cache.get_stats().cache_size,
+        path.display()
+    );
+
+    Ok(())
+}
+
+/// Print the keys of a cache matching `glob`, with the size of their values and their age.
+fn list_cache(config: &Config, name: &str, glob: Option<&str>) -> Result<(), String> {
+    let cache = open_cache(config, name)?;
+
+    let mut keys = cache
+        .keys()
+        .filter(|key| glob.is_none_or(|glob| glob_match(glob, key)))
+        .collect::<Vec<_>>();
+    keys.sort();
+
+    let mut total = 0;
+    for key in &keys {
+        let Some((value, meta)) = cache.peek(key) else {
+            continue;
+        };
+        total += value.len() as u64;
+
+        let age = meta.map_or_else(|| "?".to_string(), |meta| format_age(meta.age()));
+        println!("{:>10} {age:>6} {key}", format_size(value.len() as u64));
+    }
+
+    println!(
+        "{} of {} entries, {}",
+        keys.len(),
+        cache.get_stats().cache_size,
+        format_size(total)
+    );
+
+    Ok(())
+}
+
+/// Print a cache entry along with how it was fetched.
+fn show_cache_entry(config: &Config, name: &str, key: &str) -> Result<(), String> {
+    let cache = open_cache(config, name)?;
+
+    let Some((value, meta)) = cache.peek(key) else {
+        return Err(format!("`{key}` is not in `{name}`"));
+    };
+
+    println!("key: {key}");
+    println!("size: {}", format_size(value.len() as u64));
+    if let Some(meta) = meta {
+        println!("age: {}", format_age(meta.age()));
+        println!("fresh: {}", cache.is_fresh(key));
+        if let Some(status) = meta.status {
+            println!("status: {status}");
+        }
+        if let Some(etag) = &meta.etag {
+            println!("etag: {etag}");
+        }
+        if let Some(last_modified) = &meta.last_modified {
+            println!("last modified: {last_modified}");
+        }
+    }
+    println!("\n{value}");
+
+    Ok(())
+}
+
+/// Delete `pattern` from a cache, or every key matching it as a glob if there is no such key.
+fn delete_cache_entries(config: &Config, name: &str, pattern: &str) -> Result<(), String> {
+    let cache = open_cache(config, name)?;
+
+    let deleted = if cache.remove(pattern).is_some() {
+        1
+    } else {
+        cache
+            .keys()
+            .filter(|key| glob_match(pattern, key))
+            .filter(|key| cache.remove(key).is_some())
+            .count()
+    };
+    cache.dump();
+
+    println!("deleted {deleted} entries from `{name}`");
+
+    Ok(())
+}
+
+/// The id of the item a key like `https://booth.pm/en/items/123.json` belongs to.
+fn item_id(key: &str) -> Option<u32> {
+    let (_, rest) = key.split_once("/items/")?;
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+
+    rest[..end].parse().ok()
+}
+
+/// Delete the cached items that are not on the wishlist anymore, going by the cached wishlist pages.
+fn prune_caches(config: &Config) -> Result<(), String> {
+    const NO_PAGES: &str = "there are no cached wishlist pages, run `archive` first";
+
+    let wishlist =
+        open_cache(config, "get_all_wishlist_pages").map_err(|_| NO_PAGES.to_string())?;
+    let wishlisted = wishlist
+        .keys()
+        .filter_map(|key| wishlist.peek(&key))
+        .filter_map(|(page, _)| serde_json::from_str::<WishListNameItemsResponse>(&page).ok())
+        .flat_map(|page| get_all_item_numbers_on_page(&page))
+        .collect::<HashSet<_>>();
+
+    if wishlisted.is_empty() {
+        return Err(NO_PAGES.to_string());
+    }
+
+    let names = cache_files()
+        .iter()
+        .map(|path| cache_name(path))
+        .collect::<HashSet<_>>();
+    for name in ITEM_CACHES.into_iter().filter(|name| names.contains(*name)) {
+        let cache = open_cache(config, name)?;
+        let pruned = cache
+            .keys()
+            .filter(|key| item_id(key).is_some_and(|id| !wishlisted.contains(&id)))
+            .filter(|key| cache.remove(key).is_some())
+            .count();
+        cache.dump();
+
+        println!("pruned {pruned} entries from `{name}`");
+    }
+
+    Ok(())
+}
+
+/// Drop stale metadata and shrink the cache called `name`, or every cache.
+fn vacuum_caches(config: &Config, name: Option<&str>) -> Result<(), String> {
+    let paths = match name {
+        Some(name) => vec![open_cache(config, name)?.get_path_to_cache()],
+        None => cache_files(),
+    };
+
+    for path in paths {
+        let old_size = cache_size(&path);
+        let mut cache: Cache =
+            Cache::new_with_path(path.clone()).with_flush_policy(FlushPolicy::manual());
+        cache.vacuum();
+        drop(cache);
+        let new_size = cache_size(&path);
+
+        println!(
+            "{}: {} => {}",
+            path.display(),
+            format_size(old_size),
+            format_size(new_size)
+        );
+    }
+
+    Ok(())
+}
+
+/// Convert the ron caches in `cache/` to compressed ones and print how much space that saved.
+///
+/// Only runs with `cache_format: RonZstd`, so `archive` keeps finding the converted caches.
+fn compress_caches(config: &Config) {
+    if config.cache_format != CacheFormat::RonZstd {
+        println!(
+            "set `cache_format: RonZstd` in config.ron first, \
+            otherwise `archive` would not find the compressed caches and download everything again"
+        );
+        return;
+    }
+
+    let mut paths = fs::read_dir(CACHE_DIR)
+        .unwrap_or_else(|e| panic!("failed to read `{CACHE_DIR}` because of error: {e}"))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.ends_with(".ron") && !name.ends_with(".meta.ron")
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let (mut total_old, mut total_new) = (0, 0);
+    for path in paths {
+        let Some((new_path, old_size, new_size)) = migrate(&path, CacheFormat::RonZstd) else {
+            continue;
+        };
+        total_old += old_size;
+        total_new += new_size;
+
+        println!(
+            "{} => {}: {} => {} ({})",
+            path.display(),
+            new_path.display(),
+            format_size(old_size),
+            format_size(new_size),
+            format_savings(old_size, new_size)
+        );
+    }
+
+    println!(
+        "total: {} => {} ({})",
+        format_size(total_old),
+        format_size(total_new),
+        format_savings(total_old, total_new)
+    );
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Format an age in its largest whole unit, like `parse_duration` reads them.
+fn format_age(age: Duration) -> String {
+    const UNITS: [(u64, &str); 4] = [
+        (7 * 24 * 60 * 60, "w"),
+        (24 * 60 * 60, "d"),
+        (60 * 60, "h"),
+        (60, "m"),
+    ];
+
+    let secs = age.as_secs();
+    UNITS.iter().find(|(unit, _)| secs >= *unit).map_or_else(
+        || format!("{secs}s"),
+        |(unit, suffix)| format!("{}{suffix}", secs / unit),
+    )
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn format_savings(old_size: u64, new_size: u64) -> String {
+    if old_size == 0 {
+        return "nothing to compress".to_string();
+    }
+
+    let saved = 100.0 - new_size as f64 / old_size as f64 * 100.0;
+    format!("{saved:.1}% smaller")
+}
+
 #[allow(clippy::too_many_lines)]
 // this is the main function and i do everything in here
-fn main() {
+fn archive(config: &Config, refresh_older_than: Option<Duration>) {
     let start = Instant::now();
 
     let cookie = fs::read_to_string("cookie.txt").unwrap();
@@ -29,7 +525,11 @@ fn main() {
     let client = WebScraper::new(cookie, true);
 
     let (wishlist_pages, _) = time_it!(at once | "getting wishlist pages" => {
-            let (pages, changed) = get_all_wishlist_pages(&client);
+            let (pages, changed) = get_all_wishlist_pages(
+                &client,
+                config.cache_path("get_all_wishlist_pages"),
+                CacheTtls::max_age(config.cache_ttls.wishlist_pages, refresh_older_than),
+            );
             debug!(pages.len());
             (pages, changed)
         }
@@ -47,14 +547,14 @@ fn main() {
     });
     debug!(all_item_numbers.len());
 
-    let mut path_to_cache = PathBuf::new();
-    path_to_cache.push("cache");
-    path_to_cache.push("all_items.ron");
+    let path_to_cache = config.cache_path("all_items");
+    let ttl = CacheTtls::max_age(config.cache_ttls.items, refresh_older_than);
 
-    let cache = Arc::new(RwLock::new(Cache::new_with_path(path_to_cache)));
+    // items are parsed when they are fetched, invalid ones never make it into the cache
+    let cache: Arc<Cache<String, Json<ItemApiResponse>>> =
+        Arc::new(Cache::new_with_budget(path_to_cache, config.cache_memory_budget()).with_ttl(ttl));
 
     let client_get_one_errs = Arc::new(Mutex::new(vec![]));
-    let serde_json_errs = Arc::new(Mutex::new(vec![]));
 
     let all_items = time_it!(at once | "extracting items" => all_item_numbers
         .par_iter()
@@ -62,22 +562,13 @@ fn main() {
         .map(|id| format!("https://booth.pm/en/items/{}.json", id))
         .filter_map(|url| {
             match client.get_one(url, Some(cache.clone())) {
-                Ok(item) => Some(item),
+                Ok(Json(item)) => Some(item),
                 Err(err) => {
                     client_get_one_errs.clone().lock().unwrap().push(err);
                     None
                 }
             }
         })
-        .filter_map(|item| {
-            match serde_json::from_str::<ItemApiResponse>(&item) {
-                Ok(item) => Some(item),
-                Err(err) => {
-                    serde_json_errs.clone().lock().unwrap().push(err);
-                    None
-                }
-            }
-        })
         .collect::<Vec<ItemApiResponse>>()
     );
 
@@ -93,23 +584,11 @@ fn main() {
         debug!(client_get_one_errs.len());
     }
 
-    if !serde_json_errs.lock().unwrap().is_empty() {
-        let serde_json_errs = serde_json_errs
-            .clone()
-            .lock()
-            .unwrap()
-            .iter()
-            .map(|err| err.to_string())
-            .collect::<Vec<_>>();
-        write_items_to_file!(serde_json_errs);
-        debug!(serde_json_errs.len());
-    }
-
     debug!(all_items.len());
 
     write_items_to_file!(all_items);
 
-    let item_rows = time_it!(at once | "converting items to item rows" => {
+    let mut item_rows = time_it!(at once | "converting items to item rows" => {
         all_items
             .par_iter()
             .progress_with(get_pb(all_items.len() as u64, "converting items to Item Rows"))
@@ -117,61 +596,122 @@ fn main() {
             .collect::<Vec<ItemRow>>()
     });
 
-    let mut path_to_cache = PathBuf::new();
-    path_to_cache.push("cache");
-    path_to_cache.push("translation.ron");
+    time_it!("extracting compatible avatars" => {
+        AvatarMatcher::new(&config.avatars).apply(&mut item_rows);
+    });
+
+    let path_to_cache = config.cache_path("prices");
 
-    let translation_cache = Arc::new(RwLock::new(Cache::new_with_path(path_to_cache)));
+    let prices = Cache::new_with_path(path_to_cache);
 
-    let strings = time_it!("extracting strings from item rows" => {
+    time_it!("tracking price changes" => {
         item_rows
-            .iter()
-            .flat_map(|item_row| {
-                let markdown_strings = item_row
-                    .markdown
-                    .split('\n')
-                    .map(|x| x.to_string())
-                    .collect::<Vec<_>>();
-                let mut strings = vec![item_row.author_name.clone(), item_row.item_name.clone()];
-                strings.extend(markdown_strings);
-                strings
-            })
-            .collect::<Vec<_>>()
+            .iter_mut()
+            .for_each(|item_row| item_row.track_price(&prices));
+        prices.dump();
     });
 
-    time_it!("dumping translation cache" => translation_cache.write().unwrap().dump());
+    let path_to_cache = config.cache_path("translation");
+
+    let ttl = config.cache_ttls.translations.map(Duration::from_secs);
 
-    let initial_translation_cache_stats = translation_cache.read().unwrap().get_stats();
+    let translation_cache =
+        Cache::new_with_budget(path_to_cache, config.cache_memory_budget()).with_ttl(ttl);
+
+    time_it!("dumping translation cache" => translation_cache.dump());
+
+    let initial_translation_cache_stats = translation_cache.get_stats();
 
     write_items_to_file!(initial_translation_cache_stats);
 
-    time_it!("dumping translation cache" => translation_cache.write().unwrap().dump());
+    let glossaries = Glossaries::load(&config.glossary_path, &config.shop_glossaries_dir);
+    let translator = config.translator.as_ref().map(TranslatorConfig::build);
+
+    if translator.is_some() || !glossaries.is_empty() {
+        let segmenter = Segmenter::new(config.all_protected_words());
+        let pipeline = TranslationPipeline {
+            translator: translator.as_deref(),
+            glossaries: &glossaries,
+            segmenter: &segmenter,
+            cache: &translation_cache,
+            target: &config.target_lang,
+        };
+
+        let translation_errs = time_it!(at once | "translating item rows" => {
+            pipeline.translate_item_rows(&mut item_rows)
+        });
+
+        if !translation_errs.is_empty() {
+            write_items_to_file!(translation_errs);
+            debug!(translation_errs.len());
+        }
+    }
+
+    time_it!("applying translation overrides" => {
+        Overrides::load(&config.overrides_path).apply(&mut item_rows);
+    });
+
+    time_it!("dumping translation cache" => translation_cache.dump());
 
-    let final_translation_cache_stats = translation_cache.read().unwrap().get_stats();
+    let final_translation_cache_stats = translation_cache.get_stats();
 
     write_items_to_file!(final_translation_cache_stats);
 
+    write_items_to_file!(item_rows);
+
+    let thumbnails = if config.thumbnails {
+        time_it!(at once | "downloading thumbnails" => {
+            download_thumbnails(&client, item_rows.as_slice(), &PathBuf::from("cache/thumbnails"))
+        })
+    } else {
+        vec![]
+    };
+
     time_it!(at once | "writing items to xlsx" => {
         let mut workbook = Workbook::new();
+
+        write_summary_sheet(workbook.add_worksheet(), item_rows.as_slice()).unwrap();
+
         let worksheet = workbook.add_worksheet();
+        worksheet.set_name(ITEMS_SHEET).unwrap();
 
         write_headers(worksheet).unwrap();
 
-        write_all(worksheet, item_rows.as_slice());
+        write_all(worksheet, item_rows.as_slice(), config.bilingual);
+
+        format_cols(worksheet, item_rows.as_slice()).unwrap();
+
+        if config.thumbnails {
+            insert_thumbnails(worksheet, thumbnails.as_slice()).unwrap();
+        }
 
-        format_cols(worksheet).unwrap();
+        write_variations_sheet(workbook.add_worksheet(), item_rows.as_slice()).unwrap();
+        write_shops_sheet(workbook.add_worksheet(), item_rows.as_slice()).unwrap();
+        write_tags_sheet(workbook.add_worksheet(), item_rows.as_slice()).unwrap();
 
-        save_book(&mut workbook, "temp/book.xlsx");
+        save_book(&mut workbook, &config.output_path);
     });
 
-    time_it!("dumping cache" => cache.write().unwrap().dump());
+    if let Some(html_path) = &config.html_path {
+        time_it!("writing items to html" => {
+            write_html(item_rows.as_slice(), html_path, config.bilingual);
+        });
+    }
+
+    if let Some(markdown_path) = &config.markdown_path {
+        time_it!("writing items to markdown" => {
+            write_markdown(item_rows.as_slice(), markdown_path, config.bilingual);
+        });
+    }
+
+    time_it!("dumping cache" => cache.dump());
 
-    let cache_stats = cache.read().unwrap().get_stats();
+    let cache_stats = cache.get_stats();
     debug!(&cache_stats);
 
     write_items_to_file!(cache_stats);
 
-    let cache_misses = cache.read().unwrap().get_misses();
+    let cache_misses = cache.get_misses();
     if !cache_misses.is_empty() {
         debug!(cache_misses.len());
         debug!(cache_misses);
@@ -185,9 +725,9 @@ fn main() {
             cache_stats.cache_hits, cache_stats.cache_misses, cache_stats.cache_size,
         );
 
-        let misses = cache.read().unwrap().get_misses();
-        let hits = cache.read().unwrap().get_hits();
-        let all = cache.read().unwrap().keys().collect::<Vec<_>>();
+        let misses = cache.get_misses();
+        let hits = cache.get_hits();
+        let all = cache.keys().collect::<Vec<_>>();
 
         let missing = all
             .iter()
@@ -0,0 +1,177 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::item_row::ItemRow;
+
+/// Lines that start a section listing the supported avatars.
+const SECTION_MARKERS: &[&str] = &[
+    "対応アバター",
+    "対応モデル",
+    "対応素体",
+    "対応機種",
+    "supported avatar",
+    "compatible avatar",
+    "supported model",
+    "compatible model",
+];
+
+/// Words that mark an avatar name right before them as supported, e.g. `桔梗対応` or `桔梗用`.
+const SUFFIX_MARKERS: &[&str] = &["対応", "用", "向け"];
+
+/// Glyphs that start a new heading in Booth descriptions, ending the current section.
+const HEADING_GLYPHS: &[char] = &['■', '□', '◆', '◇', '【', '＜', '<', '#', '●', '★'];
+
+/// A base avatar items can be made for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Avatar {
+    /// the name shown in exports
+    pub name: String,
+    /// every spelling to recognize, Japanese and English
+    pub aliases: Vec<String>,
+}
+
+impl Avatar {
+    fn new(name: &str, aliases: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            aliases: aliases.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+/// The avatars recognized by default.
+#[must_use]
+pub fn default_avatars() -> Vec<Avatar> {
+    vec![
+        Avatar::new("Kikyo", &["桔梗", "Kikyo"]),
+        Avatar::new("Rindo", &["竜胆", "Rindo"]),
+        Avatar::new("Selestia", &["セレスティア", "Selestia"]),
+        Avatar::new("Manuka", &["マヌカ", "Manuka"]),
+        Avatar::new("Maya", &["舞夜"]),
+        Avatar::new("Shinra", &["森羅", "Shinra"]),
+        Avatar::new("Rusk", &["ラスク", "Rusk"]),
+        Avatar::new("Chiffon", &["シフォン", "Chiffon"]),
+        Avatar::new("Karin", &["カリン", "Karin"]),
+        Avatar::new("Airi", &["愛莉", "Airi"]),
+        Avatar::new("Yuuko", &["幽狐", "Yuuko"]),
+        Avatar::new("Hakka", &["薄荷", "Hakka"]),
+    ]
+}
+
+/// Finds the avatars an item supports in its tags and description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvatarMatcher {
+    /// (avatar name, lowercase alias)
+    aliases: Vec<(String, String)>,
+}
+
+impl AvatarMatcher {
+    #[must_use]
+    pub fn new(avatars: &[Avatar]) -> Self {
+        let aliases = avatars
+            .iter()
+            .flat_map(|avatar| {
+                avatar
+                    .aliases
+                    .iter()
+                    .filter(|alias| !alias.is_empty())
+                    .map(|alias| (avatar.name.clone(), alias.to_lowercase()))
+            })
+            .collect();
+
+        Self { aliases }
+    }
+
+    /// The names of the avatars supported according to the tags and description.
+    ///
+    /// Tags are matched as a whole, the description only in sections like `対応アバター`
+    /// and where a name is directly followed by a marker like `対応`.
+    ///
+    /// # Example
+    /// ```
+    /// use booth_archiver::models::avatars::{default_avatars, AvatarMatcher};
+    ///
+    /// let matcher = AvatarMatcher::new(&default_avatars());
+    /// let description = "■対応アバター\n・桔梗\n・Selestia\n\n■その他\nマヌカでも撮影しました\n竜胆用の調整済み";
+    ///
+    /// assert_eq!(
+    ///     matcher.extract(&["カリン".to_string()], description),
+    ///     vec!["Kikyo", "Rindo", "Selestia", "Karin"]
+    /// );
+    /// ```
+    #[must_use]
+    pub fn extract(&self, tags: &[String], description: &str) -> Vec<String> {
+        let mut texts = tags
+            .iter()
+            .map(|tag| tag.to_lowercase())
+            .collect::<Vec<_>>();
+        texts.extend(sections(description));
+
+        let description = description.to_lowercase();
+
+        let mut found = vec![];
+        for (name, alias) in &self.aliases {
+            if found.contains(name) {
+                continue;
+            }
+
+            let in_texts = texts.iter().any(|text| contains_word(text, alias));
+            let with_suffix = SUFFIX_MARKERS
+                .iter()
+                .any(|suffix| contains_word(&description, &format!("{alias}{suffix}")));
+
+            if in_texts || with_suffix {
+                found.push(name.clone());
+            }
+        }
+
+        found
+    }
+
+    /// Fill in the compatible avatars of every item row.
+    pub fn apply(&self, item_rows: &mut [ItemRow]) {
+        item_rows.par_iter_mut().for_each(|item_row| {
+            item_row.compatible_avatars = self.extract(&item_row.tags, &item_row.markdown);
+        });
+    }
+}
+
+/// The lowercase lines of the description's supported avatar sections, without their headings.
+///
+/// A section runs from its heading to the next blank line or heading.
+fn sections(description: &str) -> Vec<String> {
+    let mut lines = vec![];
+    let mut in_section = false;
+
+    for line in description.lines() {
+        let line = line.trim().to_lowercase();
+
+        if let Some(marker) = SECTION_MARKERS.iter().find(|marker| line.contains(*marker)) {
+            in_section = true;
+            // avatars listed on the heading line itself, e.g. `対応アバター：桔梗、竜胆`
+            if let Some((_, rest)) = line.split_once(marker) {
+                lines.push(rest.to_string());
+            }
+            continue;
+        }
+
+        if line.is_empty() || line.starts_with(HEADING_GLYPHS) {
+            in_section = false;
+        }
+
+        if in_section {
+            lines.push(line);
+        }
+    }
+
+    lines
+}
+
+/// Whether `text` contains `word`, not as part of a longer latin word.
+fn contains_word(text: &str, word: &str) -> bool {
+    let is_latin = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+
+    text.match_indices(word).any(|(idx, _)| {
+        !is_latin(text[..idx].chars().last()) && !is_latin(text[idx + word.len()..].chars().next())
+    })
+}
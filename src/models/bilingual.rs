@@ -0,0 +1,88 @@
+/// A line of the original text with its translation,
+/// `None` if the line has no translation or the translation is the same as the original.
+pub type BilingualLine = (String, Option<String>);
+
+/// Pair every line of `original` with the matching line of `translated`.
+///
+/// Translations are done line by line, so the lines of both texts line up.
+///
+/// # Example
+/// ```
+/// use booth_archiver::models::bilingual::interleave;
+///
+/// let lines = interleave("■ 同梱物\nUnity package\n", "■ Contents\nUnity package\n");
+/// assert_eq!(
+///     lines,
+///     vec![
+///         ("■ 同梱物".to_string(), Some("■ Contents".to_string())),
+///         ("Unity package".to_string(), None),
+///         (String::new(), None),
+///     ]
+/// );
+/// ```
+#[must_use]
+pub fn interleave(original: &str, translated: &str) -> Vec<BilingualLine> {
+    let mut translated = translated.split('\n');
+
+    original
+        .split('\n')
+        .map(|line| {
+            let translation = translated
+                .next()
+                .filter(|translation| translation.trim() != line.trim())
+                .map(ToString::to_string);
+            (line.to_string(), translation)
+        })
+        .collect()
+}
+
+/// Plain text with each translated line right below its original, marked with `»`.
+#[must_use]
+pub fn render_text(lines: &[BilingualLine]) -> String {
+    lines
+        .iter()
+        .map(|(original, translation)| match translation {
+            Some(translation) => format!("{original}\n» {translation}"),
+            None => original.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Markdown with each translated line right below its original, as a quote.
+#[must_use]
+pub fn render_markdown(lines: &[BilingualLine]) -> String {
+    lines
+        .iter()
+        .map(|(original, translation)| match translation {
+            Some(translation) => format!("{original}  \n> {translation}\n"),
+            None => format!("{original}  "),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Html with each original line and its translation side by side.
+#[must_use]
+pub fn render_html(lines: &[BilingualLine]) -> String {
+    lines
+        .iter()
+        .map(|(original, translation)| {
+            format!(
+                "<div class=\"line\"><span class=\"original\">{}</span>\
+                <span class=\"translation\">{}</span></div>",
+                escape_html(original),
+                escape_html(translation.as_deref().unwrap_or_default())
+            )
+        })
+        .collect()
+}
+
+/// Escape the characters that mean something in html.
+#[must_use]
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
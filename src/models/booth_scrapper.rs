@@ -1,7 +1,8 @@
 use std::fs;
 use std::fs::File;
 use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
+use std::sync::Arc;
+use std::time::Duration;
 
 use ron::ser::PrettyConfig;
 
@@ -14,7 +15,7 @@ fn get_last_page_number(client: &WebScraper) -> u32 {
     let document = client
         .get_one(
             "https://accounts.booth.pm/wish_list_name_items.json?page=1".to_string(),
-            None,
+            None::<Arc<Cache>>,
         )
         .unwrap_or_else(|e| panic!("failed to get wishlist page because of error: {}", e));
     let document =
@@ -40,7 +41,11 @@ fn get_last_page_number(client: &WebScraper) -> u32 {
 
 /// Get all the wishlist pages.
 #[must_use]
-pub fn get_all_wishlist_pages(client: &WebScraper) -> (Vec<String>, bool) {
+pub fn get_all_wishlist_pages(
+    client: &WebScraper,
+    cache_path: PathBuf,
+    ttl: Option<Duration>,
+) -> (Vec<String>, bool) {
     let prev_last_page = if fs::metadata("cache/last_page.ron").is_ok() {
         ron::de::from_reader(File::open("cache/last_page.ron").unwrap()).unwrap()
     } else {
@@ -51,15 +56,11 @@ pub fn get_all_wishlist_pages(client: &WebScraper) -> (Vec<String>, bool) {
 
     let last_page_changed = prev_last_page != last_page;
 
-    let mut cache_path = PathBuf::new();
-    cache_path.push("cache");
-    cache_path.push("get_all_wishlist_pages.ron");
-
-    let cache = Arc::new(RwLock::new(Cache::new_with_path(cache_path)));
+    let cache = Arc::new(Cache::new_with_path(cache_path).with_ttl(ttl));
 
     if last_page_changed {
         println!("last page changed, clearing cache");
-        cache.write().unwrap().clear();
+        cache.clear();
     }
 
     let urls = (1..=last_page)
@@ -78,7 +79,7 @@ pub fn get_all_wishlist_pages(client: &WebScraper) -> (Vec<String>, bool) {
         .cloned()
         .collect();
 
-    cache.read().unwrap().dump();
+    cache.dump();
 
     assert_eq!(Arc::strong_count(&cache), 1);
 
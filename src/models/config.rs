@@ -0,0 +1,185 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::avatars::{default_avatars, Avatar};
+use crate::models::translator::TranslatorConfig;
+use crate::zaphkiel::backend::CacheFormat;
+
+/// User configuration, read from a ron file.
+/// Every field is optional in the file, missing fields fall back to their defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// where to save the xlsx export
+    pub output_path: PathBuf,
+    /// where to save the html export, no html is written if this is not set
+    pub html_path: Option<PathBuf>,
+    /// where to save the markdown export, no markdown is written if this is not set
+    pub markdown_path: Option<PathBuf>,
+    /// show translated descriptions line by line under the original instead of on their own
+    pub bilingual: bool,
+    /// embed a thumbnail of every item in the xlsx export, turn this off for huge exports
+    pub thumbnails: bool,
+    /// the translation service to translate item names, shop names and descriptions with,
+    /// nothing is translated if this is not set
+    pub translator: Option<TranslatorConfig>,
+    /// glossary applied before, or without, the translator
+    pub glossary_path: PathBuf,
+    /// directory of per shop glossaries, named after the shop's subdomain
+    pub shop_glossaries_dir: PathBuf,
+    /// hand written translations, they take precedence over the translator
+    pub overrides_path: PathBuf,
+    /// words the translator must leave alone, e.g. software names, avatar names are always protected
+    pub protected_words: Vec<String>,
+    /// avatars to recognize in item tags and descriptions
+    pub avatars: Vec<Avatar>,
+    /// language to translate the items to
+    pub target_lang: String,
+    /// storage format of the caches in `cache/`
+    pub cache_format: CacheFormat,
+    /// how long cached data stays fresh
+    pub cache_ttls: CacheTtls,
+    /// how many MiB of values each cache keeps in memory, the least recently used are read
+    /// back from disk when needed again, ron caches keep their values in a redb file next to them
+    /// while they are open to do that, see `CacheFormat::open_with_budget`
+    pub cache_memory_budget_mib: Option<u64>,
+}
+
+/// How long the entries of each cache stay fresh, in seconds, `None` keeps them forever.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheTtls {
+    pub wishlist_pages: Option<u64>,
+    pub items: Option<u64>,
+    pub translations: Option<u64>,
+}
+
+impl Default for CacheTtls {
+    fn default() -> Self {
+        Self {
+            wishlist_pages: Some(60 * 60),
+            items: Some(24 * 60 * 60),
+            translations: None,
+        }
+    }
+}
+
+impl CacheTtls {
+    /// The ttl of the cache called `name`, e.g. `all_items`, caches without one keep their entries forever.
+    #[must_use]
+    pub fn of(&self, name: &str) -> Option<u64> {
+        match name {
+            "get_all_wishlist_pages" => self.wishlist_pages,
+            "all_items" => self.items,
+            "translation" => self.translations,
+            _ => None,
+        }
+    }
+
+    /// The ttl of a cache, shortened to `refresh_older_than` if that is shorter.
+    #[must_use]
+    pub fn max_age(ttl: Option<u64>, refresh_older_than: Option<Duration>) -> Option<Duration> {
+        let ttl = ttl.map(Duration::from_secs);
+
+        match (ttl, refresh_older_than) {
+            (Some(ttl), Some(refresh)) => Some(ttl.min(refresh)),
+            (ttl, refresh) => ttl.or(refresh),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            output_path: PathBuf::from("temp/book.xlsx"),
+            html_path: None,
+            markdown_path: None,
+            bilingual: false,
+            thumbnails: true,
+            translator: None,
+            glossary_path: PathBuf::from("custom_translations.ron"),
+            shop_glossaries_dir: PathBuf::from("glossaries"),
+            overrides_path: PathBuf::from("translation_overrides.ron"),
+            protected_words: ["VRChat", "Unity", "VRM", "VN3", "BOOTH"]
+                .map(ToString::to_string)
+                .to_vec(),
+            avatars: default_avatars(),
+            target_lang: "en".to_string(),
+            cache_format: CacheFormat::default(),
+            cache_ttls: CacheTtls::default(),
+            cache_memory_budget_mib: None,
+        }
+    }
+}
+
+impl Config {
+    /// Load the config from the given file, or the default config if the file does not exist.
+    #[must_use]
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+
+        fs::read_to_string(path).map_or_else(
+            |_| Self::default(),
+            |config| {
+                ron::from_str(&config).unwrap_or_else(|e| {
+                    panic!(
+                        "Failed to parse {}, the ron data is invalid\n\
+                        Failed with error: {}",
+                        path.display(),
+                        e
+                    )
+                })
+            },
+        )
+    }
+
+    /// The path of the cache with the given name, in the configured format.
+    #[must_use]
+    pub fn cache_path(&self, name: &str) -> PathBuf {
+        let mut path = PathBuf::new();
+        path.push("cache");
+        path.push(name);
+        path.set_extension(self.cache_format.extension());
+
+        path
+    }
+
+    /// The memory budget of each cache in bytes.
+    #[must_use]
+    pub fn cache_memory_budget(&self) -> Option<usize> {
+        self.cache_memory_budget_mib
+            .map(|mib| usize::try_from(mib.saturating_mul(1024 * 1024)).unwrap_or(usize::MAX))
+    }
+
+    /// The words the translator must leave alone, including every avatar name and alias,
+    /// so e.g. 桔梗 is not translated to "bellflower".
+    #[must_use]
+    pub fn all_protected_words(&self) -> Vec<String> {
+        let mut words = self.protected_words.clone();
+        for avatar in &self.avatars {
+            words.push(avatar.name.clone());
+            words.extend(avatar.aliases.iter().cloned());
+        }
+        words.sort();
+        words.dedup();
+
+        words
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::segmenter::Segmenter;
+
+    #[test]
+    fn japanese_avatar_aliases_are_protected() {
+        let segmenter = Segmenter::new(Config::default().all_protected_words());
+        let segmented = segmenter.segment("桔梗ちゃん、竜胆くん用の衣装です");
+
+        assert_eq!(segmented.translatable(), ["ちゃん、", "くん用の衣装です"]);
+    }
+}
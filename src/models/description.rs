@@ -0,0 +1,292 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::translation::is_url_char;
+
+/// Glyphs that mark a heading line, e.g. `■ 内容` or `◆ 利用規約 ◆`.
+const HEADING_GLYPHS: &[char] = &['■', '□', '◆', '◇', '◼', '◾'];
+
+/// Glyphs that start a bullet list item.
+const BULLETS: &[char] = &['・', '･', '-', '*', '•', '●', '○'];
+
+/// Characters decorating separator lines like `━━━━` or `=====`.
+const SEPARATORS: &[char] = &[
+    '━', '─', '-', '=', '＝', '~', '〜', '*', '_', '◆', '◇', '■', '□',
+];
+
+const TERMS_KEYWORDS: &[&str] = &[
+    "利用規約",
+    "規約",
+    "使用許諾",
+    "ライセンス",
+    "禁止事項",
+    "license",
+    "licence",
+    "terms",
+];
+
+const CHANGELOG_KEYWORDS: &[&str] = &[
+    "更新履歴",
+    "更新情報",
+    "変更履歴",
+    "アップデート",
+    "changelog",
+    "change log",
+    "update",
+    "version history",
+];
+
+/// What a section of a description is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SectionKind {
+    /// text before the first heading
+    Intro,
+    TermsOfUse,
+    Changelog,
+    Other,
+}
+
+/// A run of lines in a section.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Block {
+    Paragraph(String),
+    List(Vec<String>),
+}
+
+/// A heading and everything up to the next one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Section {
+    pub heading: Option<String>,
+    pub kind: SectionKind,
+    pub blocks: Vec<Block>,
+}
+
+/// Where an external link points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LinkKind {
+    Twitter,
+    License,
+    Booth,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Link {
+    pub url: String,
+    pub kind: LinkKind,
+}
+
+/// An item description split into sections and links.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Description {
+    pub sections: Vec<Section>,
+    pub links: Vec<Link>,
+}
+
+impl Section {
+    fn new(heading: Option<String>) -> Self {
+        let kind = heading.as_deref().map_or(SectionKind::Intro, section_kind);
+
+        Self {
+            heading,
+            kind,
+            blocks: vec![],
+        }
+    }
+
+    fn push_line(&mut self, line: &str) {
+        if let Some(item) = bullet_item(line) {
+            if let Some(Block::List(items)) = self.blocks.last_mut() {
+                items.push(item.to_string());
+            } else {
+                self.blocks.push(Block::List(vec![item.to_string()]));
+            }
+        } else if let Some(Block::Paragraph(paragraph)) = self.blocks.last_mut() {
+            paragraph.push('\n');
+            paragraph.push_str(line);
+        } else {
+            self.blocks.push(Block::Paragraph(line.to_string()));
+        }
+    }
+
+    /// The section's text, without its heading.
+    #[must_use]
+    pub fn text(&self) -> String {
+        self.blocks
+            .iter()
+            .map(|block| match block {
+                Block::Paragraph(paragraph) => paragraph.clone(),
+                Block::List(items) => items
+                    .iter()
+                    .map(|item| format!("・{item}"))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+impl Description {
+    /// Parse a Booth description.
+    ///
+    /// # Example
+    /// ```
+    /// use booth_archiver::models::description::{Block, Description, LinkKind, SectionKind};
+    ///
+    /// let description = Description::parse(
+    ///     "可愛い衣装です\n\n■ 内容\n・fbx\n・unitypackage\n\n【利用規約】\nhttps://www.vn3.org/terms\n\n◆更新履歴◆\n2023/01/01 v1.1 色を追加",
+    /// );
+    ///
+    /// let kinds = description.sections.iter().map(|s| s.kind).collect::<Vec<_>>();
+    /// assert_eq!(
+    ///     kinds,
+    ///     [SectionKind::Intro, SectionKind::Other, SectionKind::TermsOfUse, SectionKind::Changelog]
+    /// );
+    /// assert_eq!(
+    ///     description.sections[1].blocks,
+    ///     [Block::List(vec!["fbx".to_string(), "unitypackage".to_string()])]
+    /// );
+    /// assert_eq!(description.links[0].kind, LinkKind::License);
+    /// assert_eq!(description.changelog().unwrap(), "2023/01/01 v1.1 色を追加");
+    /// ```
+    #[must_use]
+    pub fn parse(text: &str) -> Self {
+        let mut sections = vec![Section::new(None)];
+
+        for line in text.lines() {
+            let line = line.trim();
+
+            if let Some(heading) = heading(line) {
+                sections.push(Section::new(Some(heading)));
+            } else if !line.is_empty() && !is_separator(line) {
+                sections.last_mut().unwrap().push_line(line);
+            }
+        }
+
+        sections.retain(|section| section.heading.is_some() || !section.blocks.is_empty());
+
+        Self {
+            sections,
+            links: links(text),
+        }
+    }
+
+    /// The text of every section of the given kind.
+    #[must_use]
+    pub fn text_of(&self, kind: SectionKind) -> Option<String> {
+        let text = self
+            .sections
+            .iter()
+            .filter(|section| section.kind == kind)
+            .map(Section::text)
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// The terms of use, followed by any license links.
+    #[must_use]
+    pub fn license(&self) -> Option<String> {
+        let mut parts = self
+            .text_of(SectionKind::TermsOfUse)
+            .into_iter()
+            .collect::<Vec<_>>();
+        for link in self.links_of(LinkKind::License) {
+            if !parts.iter().any(|part| part.contains(link)) {
+                parts.push(link.to_string());
+            }
+        }
+
+        (!parts.is_empty()).then(|| parts.join("\n"))
+    }
+
+    #[must_use]
+    pub fn changelog(&self) -> Option<String> {
+        self.text_of(SectionKind::Changelog)
+    }
+
+    pub fn links_of(&self, kind: LinkKind) -> impl Iterator<Item = &str> {
+        self.links
+            .iter()
+            .filter(move |link| link.kind == kind)
+            .map(|link| link.url.as_str())
+    }
+}
+
+/// The heading text if the line is a heading, e.g. `■ 内容`, `【内容】` or `◆内容◆`.
+fn heading(line: &str) -> Option<String> {
+    let text = if line.starts_with(HEADING_GLYPHS) {
+        line.trim_matches(HEADING_GLYPHS)
+    } else if line.starts_with('【') && line.ends_with('】') {
+        &line['【'.len_utf8()..line.len() - '】'.len_utf8()]
+    } else {
+        return None;
+    };
+
+    let text = text.trim();
+    (!text.is_empty() && !is_separator(text)).then(|| text.to_string())
+}
+
+fn section_kind(heading: &str) -> SectionKind {
+    let heading = heading.to_lowercase();
+    let has = |keywords: &[&str]| keywords.iter().any(|keyword| heading.contains(keyword));
+
+    if has(TERMS_KEYWORDS) {
+        SectionKind::TermsOfUse
+    } else if has(CHANGELOG_KEYWORDS) {
+        SectionKind::Changelog
+    } else {
+        SectionKind::Other
+    }
+}
+
+fn bullet_item(line: &str) -> Option<&str> {
+    let item = line.strip_prefix(BULLETS)?.trim();
+
+    (!item.is_empty()).then_some(item)
+}
+
+fn is_separator(line: &str) -> bool {
+    line.chars().count() >= 3
+        && line
+            .chars()
+            .all(|c| SEPARATORS.contains(&c) || c.is_whitespace())
+}
+
+fn links(text: &str) -> Vec<Link> {
+    let mut links: Vec<Link> = vec![];
+
+    for (idx, _) in text.match_indices("http") {
+        let rest = &text[idx..];
+        let url = &rest[..rest.find(|c: char| !is_url_char(c)).unwrap_or(rest.len())];
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            continue;
+        }
+        if links.iter().any(|link| link.url == url) {
+            continue;
+        }
+
+        links.push(Link {
+            url: url.to_string(),
+            kind: link_kind(url),
+        });
+    }
+
+    links
+}
+
+fn link_kind(url: &str) -> LinkKind {
+    let url = url.to_lowercase();
+
+    if url.contains("twitter.com/") || url.contains("//x.com/") {
+        LinkKind::Twitter
+    } else if url.contains("vn3.org") || url.contains("license") || url.contains("terms") {
+        LinkKind::License
+    } else if url.contains("booth.pm") {
+        LinkKind::Booth
+    } else {
+        LinkKind::Other
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Phrase to phrase translations that are applied without asking a translator,
+/// read from a ron map like `custom_translations.ron`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Glossary {
+    exact: HashMap<String, String>,
+    /// phrases grouped by their first char, longest phrase first
+    phrases: HashMap<char, Vec<(String, String)>>,
+}
+
+impl Glossary {
+    /// Create a glossary from `(phrase, translation)` pairs.
+    #[must_use]
+    pub fn new(entries: HashMap<String, String>) -> Self {
+        let mut phrases: HashMap<char, Vec<(String, String)>> = HashMap::new();
+        for (phrase, translation) in &entries {
+            if let Some(first) = phrase.chars().next() {
+                phrases
+                    .entry(first)
+                    .or_default()
+                    .push((phrase.clone(), translation.clone()));
+            }
+        }
+        for phrases in phrases.values_mut() {
+            phrases.sort_by_key(|(phrase, _)| std::cmp::Reverse(phrase.len()));
+        }
+
+        Self {
+            exact: entries,
+            phrases,
+        }
+    }
+
+    /// Load the glossary from the given ron file, or an empty glossary if the file does not exist.
+    #[must_use]
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+
+        fs::read_to_string(path).map_or_else(
+            |_| Self::default(),
+            |glossary| {
+                Self::new(ron::from_str(&glossary).unwrap_or_else(|e| {
+                    panic!(
+                        "Failed to parse {}, the ron data is invalid\n\
+                        Failed with error: {}",
+                        path.display(),
+                        e
+                    )
+                }))
+            },
+        )
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.exact.is_empty()
+    }
+
+    /// A glossary with the entries of both glossaries, `other` wins on conflicts.
+    #[must_use]
+    pub fn merge(&self, other: &Self) -> Self {
+        let mut entries = self.exact.clone();
+        entries.extend(other.exact.clone());
+
+        Self::new(entries)
+    }
+
+    /// The translation of `text` if the whole text is in the glossary.
+    #[must_use]
+    pub fn exact(&self, text: &str) -> Option<String> {
+        self.exact.get(text.trim()).cloned()
+    }
+
+    /// Replace every glossary phrase in `text` with its translation,
+    /// preferring the longest phrase when several start at the same place.
+    #[must_use]
+    pub fn substitute(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(first) = rest.chars().next() {
+            let phrase = self.phrases.get(&first).and_then(|phrases| {
+                phrases
+                    .iter()
+                    .find(|(phrase, _)| rest.starts_with(phrase.as_str()))
+            });
+
+            match phrase {
+                Some((phrase, translation)) => {
+                    rest = &rest[phrase.len()..];
+
+                    // keep the translation from gluing onto neighbouring latin words
+                    if out.ends_with(|c: char| c.is_ascii_alphanumeric()) {
+                        out.push(' ');
+                    }
+                    out.push_str(translation);
+                    if rest.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+                        out.push(' ');
+                    }
+                }
+                None => {
+                    out.push(first);
+                    rest = &rest[first.len_utf8()..];
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// The global glossary together with the per shop glossaries.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Glossaries {
+    global: Glossary,
+    /// shop glossaries, already merged with the global one
+    shops: HashMap<String, Glossary>,
+}
+
+impl Glossaries {
+    /// Load the global glossary from `path` and the shop glossaries from `shops_dir`.
+    ///
+    /// Shop glossaries are named after the shop's subdomain,
+    /// e.g. `glossaries/example.ron` for `https://example.booth.pm/`.
+    #[must_use]
+    pub fn load(path: impl AsRef<Path>, shops_dir: impl AsRef<Path>) -> Self {
+        let global = Glossary::load(path);
+
+        let shops = fs::read_dir(shops_dir)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+                    .filter_map(|path| {
+                        let shop = path.file_stem()?.to_string_lossy().to_string();
+                        Some((shop, global.merge(&Glossary::load(&path))))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self { global, shops }
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.global.is_empty() && self.shops.values().all(Glossary::is_empty)
+    }
+
+    /// The glossary to use for items of the shop at `shop_url`.
+    #[must_use]
+    pub fn for_shop(&self, shop_url: &str) -> &Glossary {
+        let subdomain = shop_url
+            .split_once("://")
+            .map_or(shop_url, |(_, rest)| rest)
+            .split('.')
+            .next()
+            .unwrap_or_default();
+
+        self.shops.get(subdomain).unwrap_or(&self.global)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    fn glossary(entries: &[(&str, &str)]) -> Glossary {
+        Glossary::new(
+            entries
+                .iter()
+                .map(|(phrase, translation)| (phrase.to_string(), translation.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn exact_matches_and_longest_phrases_win() {
+        let glossary = glossary(&[
+            ("髪", "hair"),
+            ("前髪", "bangs"),
+            ("前髪セット", "bangs set"),
+        ]);
+
+        assert_eq!(glossary.exact(" 前髪 "), Some("bangs".to_string()));
+        assert_eq!(glossary.exact("前髪と髪"), None);
+
+        assert_eq!(glossary.substitute("前髪と髪"), "bangsとhair");
+        assert_eq!(glossary.substitute("前髪セット"), "bangs set");
+        assert_eq!(glossary.substitute("Kikyo用前髪"), "Kikyo用bangs");
+        assert_eq!(glossary.substitute("Kikyo髪v2"), "Kikyo hair v2");
+    }
+
+    #[test]
+    fn shop_glossaries_override_the_global_one() {
+        let dir = TempDir::new("glossaries");
+        fs::write(dir.join("global.ron"), r#"{"髪": "hair", "服": "clothes"}"#).unwrap();
+        fs::create_dir(dir.join("shops")).unwrap();
+        fs::write(dir.join("shops/example.ron"), r#"{"髪": "wig"}"#).unwrap();
+
+        let glossaries = Glossaries::load(dir.join("global.ron"), dir.join("shops"));
+
+        let shop = glossaries.for_shop("https://example.booth.pm/");
+        assert_eq!(shop.exact("髪"), Some("wig".to_string()));
+        assert_eq!(shop.exact("服"), Some("clothes".to_string()));
+
+        let other = glossaries.for_shop("https://other.booth.pm/");
+        assert_eq!(other.exact("髪"), Some("hair".to_string()));
+        assert!(std::ptr::eq(
+            glossaries.for_shop("https://example.booth.pm/items"),
+            shop
+        ));
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::models::bilingual::{escape_html, interleave, render_html};
+use crate::models::item_row::ItemRow;
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 80em; margin: auto; }
+article { border-bottom: 1px solid #ccc; padding: 1em 0; }
+img.thumbnail { float: right; width: 160px; margin-left: 1em; }
+.description { white-space: pre-wrap; clear: both; }
+.line { display: grid; grid-template-columns: 1fr 1fr; gap: 1em; }
+.translation { color: #555; }";
+
+/// Render a single item as an html article.
+///
+/// With `bilingual` set, the description shows every original line next to its translation,
+/// otherwise the translated description, falling back to the original.
+#[must_use]
+pub fn render_item(item: &ItemRow, bilingual: bool) -> String {
+    let mut out = String::from("<article>\n");
+
+    let name = item
+        .item_name_translated
+        .as_ref()
+        .unwrap_or(&item.item_name);
+    let author = item
+        .author_name_translated
+        .as_ref()
+        .unwrap_or(&item.author_name);
+
+    if let Some(thumbnail_url) = &item.thumbnail_url {
+        let _ = writeln!(
+            out,
+            "<img class=\"thumbnail\" src=\"{}\" alt=\"\">",
+            escape_html(thumbnail_url)
+        );
+    }
+    let _ = writeln!(
+        out,
+        "<h2><a href=\"{}\">{}</a></h2>",
+        escape_html(&item.item_link),
+        escape_html(name)
+    );
+    if name != &item.item_name {
+        let _ = writeln!(out, "<p lang=\"ja\">{}</p>", escape_html(&item.item_name));
+    }
+    let _ = writeln!(
+        out,
+        "<p><a href=\"{}\">{}</a> · {} {} · {} / {}</p>",
+        escape_html(&item.author_link),
+        escape_html(author),
+        item.price,
+        escape_html(&item.currency),
+        escape_html(&item.primary_category),
+        escape_html(&item.secondary_category)
+    );
+    if !item.compatible_avatars.is_empty() {
+        let _ = writeln!(
+            out,
+            "<p>Avatars: {}</p>",
+            escape_html(&item.compatible_avatars.join(", "))
+        );
+    }
+    let _ = writeln!(out, "<p>{}</p>", escape_html(&item.tags.join(", ")));
+
+    let description = match (&item.markdown_translated, bilingual) {
+        (Some(translated), true) => render_html(&interleave(&item.markdown, translated)),
+        (Some(translated), false) => escape_html(translated),
+        (None, _) => escape_html(&item.markdown),
+    };
+    let _ = writeln!(out, "<div class=\"description\">{description}</div>");
+
+    out.push_str("</article>\n");
+    out
+}
+
+/// Write all items to a single html page.
+pub fn write_html(items: &[ItemRow], path: impl AsRef<Path>, bilingual: bool) {
+    let path = path.as_ref();
+
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>Booth wishlist</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n\
+        <h1>Booth wishlist</h1>\n"
+    );
+    for item in items {
+        out.push_str(&render_item(item, bilingual));
+    }
+    out.push_str("</body>\n</html>\n");
+
+    fs::write(path, out)
+        .unwrap_or_else(|e| panic!("failed to write `{}` because of error: {e}", path.display()));
+}
@@ -3,9 +3,13 @@
 use serde::{Deserialize, Serialize};
 
 use crate::api_structs::items::ItemApiResponse;
+use crate::models::description::Description;
+use crate::zaphkiel::cache::Cache;
+use crate::zaphkiel::codec::Json;
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct ItemRow {
+    pub id: u64,
     pub item_name: String,
     pub item_name_translated: Option<String>,
     pub item_link: String,
@@ -16,19 +20,37 @@ pub struct ItemRow {
     pub secondary_category: String,
     pub vrchat: bool,
     pub adult: bool,
+    pub sold_out: bool,
     pub tags: Vec<String>,
+    pub compatible_avatars: Vec<String>,
     pub price: f64,
+    pub previous_price: Option<f64>,
     pub currency: String,
     pub hearts: u32,
     pub image_urls: Vec<String>,
+    pub thumbnail_url: Option<String>,
     pub download_links: Vec<String>,
+    pub variations: Vec<VariationRow>,
     pub markdown: String,
     pub markdown_translated: Option<String>,
+    /// the description split into sections, parsed from `markdown`
+    #[serde(default)]
+    pub description: Description,
+}
+
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct VariationRow {
+    pub name: Option<String>,
+    pub price: f64,
+    pub status: String,
+    pub sold_out: bool,
+    pub downloadables: Vec<String>,
 }
 
 #[allow(clippy::fallible_impl_from)]
 impl From<ItemApiResponse> for ItemRow {
     fn from(value: ItemApiResponse) -> Self {
+        let id = u64::try_from(value.id).unwrap();
         let item_name = value.name;
         let item_name_translated = None;
         let item_link = value.url;
@@ -42,9 +64,12 @@ impl From<ItemApiResponse> for ItemRow {
             .iter()
             .any(|tag| tag.name.to_lowercase() == "vrchat");
         let adult = value.is_adult;
+        let sold_out = value.is_sold_out;
         let tags = value.tags.iter().map(|tag| tag.name.clone()).collect();
+        let compatible_avatars = vec![];
         let price_tuple: (&str, &str) = value.price.split_once(' ').unwrap();
         let price = price_tuple.0.replace(',', "").parse().unwrap();
+        let previous_price = None;
         let currency = price_tuple.1.to_owned();
         let hearts = u32::try_from(value.wish_lists_count).unwrap();
         let image_urls = value
@@ -52,6 +77,7 @@ impl From<ItemApiResponse> for ItemRow {
             .iter()
             .map(|img| img.original.clone())
             .collect();
+        let thumbnail_url = value.images.first().map(|img| img.resized.clone());
         let download_links = value
             .variations
             .iter()
@@ -61,9 +87,31 @@ impl From<ItemApiResponse> for ItemRow {
                 })
             })
             .collect();
+        let variations = value
+            .variations
+            .iter()
+            .map(|variation| VariationRow {
+                name: variation.name.clone(),
+                price: variation.price,
+                status: variation.status.clone(),
+                sold_out: variation.is_empty_stock,
+                downloadables: variation
+                    .downloadable
+                    .iter()
+                    .flat_map(|downloadable| {
+                        downloadable
+                            .no_musics
+                            .iter()
+                            .map(|item| item.file_name.clone())
+                    })
+                    .collect(),
+            })
+            .collect();
+        let description = Description::parse(&value.description);
         let markdown = value.description;
         let markdown_translated = None;
         Self {
+            id,
             item_name,
             item_name_translated,
             item_link,
@@ -74,14 +122,34 @@ impl From<ItemApiResponse> for ItemRow {
             secondary_category,
             vrchat,
             adult,
+            sold_out,
             tags,
+            compatible_avatars,
             price,
+            previous_price,
             currency,
             hearts,
             image_urls,
+            thumbnail_url,
             download_links,
+            variations,
             markdown,
             markdown_translated,
+            description,
+        }
+    }
+}
+
+impl ItemRow {
+    /// Compare the price against the one recorded in `prices` on the previous run,
+    /// setting `previous_price` if it changed, and record the current price.
+    pub fn track_price(&mut self, prices: &Cache<String, Json<f64>>) {
+        if let Some(Json(previous_price)) = prices.get(&self.item_link) {
+            if (previous_price - self.price).abs() > f64::EPSILON {
+                self.previous_price = Some(previous_price);
+            }
         }
+
+        prices.add(self.item_link.clone(), Json(self.price));
     }
 }
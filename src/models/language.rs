@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+/// Share of the letters that have to be CJK for a text to count as CJK.
+const CJK_RATIO: f64 = 0.2;
+
+/// Kanji only texts at least this long are assumed to be Chinese rather than Japanese.
+const CHINESE_MIN_LEN: usize = 20;
+
+/// Language of a piece of text, as far as its script tells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Language {
+    Japanese,
+    Korean,
+    Chinese,
+    /// mostly latin letters, assumed to not need translating
+    Latin,
+    /// no letters at all, e.g. numbers or punctuation
+    Unknown,
+}
+
+impl Language {
+    /// Whether text in this language should be sent to the translator.
+    #[must_use]
+    pub const fn needs_translation(self) -> bool {
+        matches!(self, Self::Japanese | Self::Korean | Self::Chinese)
+    }
+
+    /// The language code translators use for this language.
+    #[must_use]
+    pub const fn code(self) -> Option<&'static str> {
+        match self {
+            Self::Japanese => Some("ja"),
+            Self::Korean => Some("ko"),
+            Self::Chinese => Some("zh"),
+            Self::Latin | Self::Unknown => None,
+        }
+    }
+}
+
+/// Guess the language of `text` from the scripts its letters are written in.
+///
+/// Any kana makes a text Japanese, mostly hangul makes it Korean,
+/// and long runs of kanji without kana make it Chinese.
+/// Shorter kanji only texts, like names, are assumed to be Japanese.
+///
+/// # Example
+/// ```
+/// use booth_archiver::models::language::{detect_language, Language};
+///
+/// assert_eq!(detect_language("対応アバター"), Language::Japanese);
+/// assert_eq!(detect_language("桔梗"), Language::Japanese);
+/// assert_eq!(detect_language("아바타 의상"), Language::Korean);
+/// assert_eq!(detect_language("Supported avatars: Kikyo"), Language::Latin);
+/// assert_eq!(detect_language("VRChat想定"), Language::Japanese);
+/// assert_eq!(detect_language("1,000 / 2,000"), Language::Unknown);
+/// ```
+#[must_use]
+pub fn detect_language(text: &str) -> Language {
+    let (mut kana, mut kanji, mut hangul, mut other) = (0usize, 0usize, 0usize, 0usize);
+
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        match c {
+            '\u{3040}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9F}' => {
+                kana += 1;
+            }
+            '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '\u{F900}'..='\u{FAFF}' => {
+                kanji += 1;
+            }
+            '\u{AC00}'..='\u{D7AF}' | '\u{1100}'..='\u{11FF}' | '\u{3130}'..='\u{318F}' => {
+                hangul += 1;
+            }
+            _ => other += 1,
+        }
+    }
+
+    let cjk = kana + kanji + hangul;
+    let letters = cjk + other;
+
+    if letters == 0 {
+        return Language::Unknown;
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    if (cjk as f64) < (letters as f64) * CJK_RATIO {
+        return Language::Latin;
+    }
+
+    if hangul > kana + kanji {
+        Language::Korean
+    } else if kana == 0 && kanji >= CHINESE_MIN_LEN {
+        Language::Chinese
+    } else {
+        Language::Japanese
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::models::bilingual::{interleave, render_markdown};
+use crate::models::item_row::ItemRow;
+
+/// Render a single item as a markdown section.
+///
+/// With `bilingual` set, the description shows every original line with its translation below,
+/// otherwise the translated description, falling back to the original.
+#[must_use]
+pub fn render_item(item: &ItemRow, bilingual: bool) -> String {
+    let mut out = String::new();
+
+    let name = item
+        .item_name_translated
+        .as_ref()
+        .unwrap_or(&item.item_name);
+    let author = item
+        .author_name_translated
+        .as_ref()
+        .unwrap_or(&item.author_name);
+
+    let _ = writeln!(out, "## [{name}]({})\n", item.item_link);
+    if name != &item.item_name {
+        let _ = writeln!(out, "*{}*\n", item.item_name);
+    }
+    if let Some(thumbnail_url) = &item.thumbnail_url {
+        let _ = writeln!(out, "![{name}]({thumbnail_url})\n");
+    }
+    let _ = writeln!(out, "- Shop: [{author}]({})", item.author_link);
+    let _ = writeln!(out, "- Price: {} {}", item.price, item.currency);
+    let _ = writeln!(
+        out,
+        "- Category: {} / {}",
+        item.primary_category, item.secondary_category
+    );
+    if !item.compatible_avatars.is_empty() {
+        let _ = writeln!(out, "- Avatars: {}", item.compatible_avatars.join(", "));
+    }
+    let _ = writeln!(out, "- Tags: {}\n", item.tags.join(", "));
+
+    let description = match (&item.markdown_translated, bilingual) {
+        (Some(translated), true) => render_markdown(&interleave(&item.markdown, translated)),
+        (Some(translated), false) => translated.clone(),
+        (None, _) => item.markdown.clone(),
+    };
+    let _ = writeln!(out, "{description}\n");
+
+    out
+}
+
+/// Write all items to a single markdown file.
+pub fn write_markdown(items: &[ItemRow], path: impl AsRef<Path>, bilingual: bool) {
+    let path = path.as_ref();
+
+    let mut out = String::from("# Booth wishlist\n\n");
+    for item in items {
+        out.push_str(&render_item(item, bilingual));
+    }
+
+    fs::write(path, out)
+        .unwrap_or_else(|e| panic!("failed to write `{}` because of error: {e}", path.display()));
+}
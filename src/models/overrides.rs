@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::models::item_row::ItemRow;
+use crate::models::language::detect_language;
+
+/// A translated field of an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Field {
+    Name,
+    ShopName,
+    Description,
+}
+
+/// Hand written translations of one item, each one replaces the machine translation.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ItemOverride {
+    pub name: Option<String>,
+    pub shop_name: Option<String>,
+    pub description: Option<String>,
+}
+
+impl ItemOverride {
+    fn field_mut(&mut self, field: Field) -> &mut Option<String> {
+        match field {
+            Field::Name => &mut self.name,
+            Field::ShopName => &mut self.shop_name,
+            Field::Description => &mut self.description,
+        }
+    }
+}
+
+/// Hand written translations keyed by item id, they take precedence over the translation cache.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Overrides {
+    items: BTreeMap<u64, ItemOverride>,
+}
+
+/// A string that still needs a human translation, as exported for manual translation.
+///
+/// Fill in `translation` and import the file back to turn it into an override.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingTranslation {
+    pub id: u64,
+    pub field: Field,
+    pub original: String,
+    /// the machine translation, if there is one
+    pub machine: Option<String>,
+    pub translation: String,
+}
+
+impl Overrides {
+    /// Load the overrides from the given ron file, or no overrides if the file does not exist.
+    #[must_use]
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+
+        fs::read_to_string(path).map_or_else(
+            |_| Self::default(),
+            |overrides| Self {
+                items: ron::from_str(&overrides).unwrap_or_else(|e| {
+                    panic!(
+                        "Failed to parse {}, the ron data is invalid\n\
+                        Failed with error: {}",
+                        path.display(),
+                        e
+                    )
+                }),
+            },
+        )
+    }
+
+    /// Save the overrides to the given ron file.
+    pub fn save(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+
+        let overrides = ron::ser::to_string_pretty(&self.items, PrettyConfig::default())
+            .expect("failed to serialize overrides to ron");
+
+        fs::write(path, overrides).unwrap_or_else(|e| {
+            panic!("failed to write `{}` because of error: {e}", path.display())
+        });
+    }
+
+    #[must_use]
+    pub fn get(&self, id: u64) -> Option<&ItemOverride> {
+        self.items.get(&id)
+    }
+
+    /// Replace the translated fields of the item rows with their overrides.
+    pub fn apply(&self, item_rows: &mut [ItemRow]) {
+        for item_row in item_rows {
+            let Some(item_override) = self.items.get(&item_row.id) else {
+                continue;
+            };
+
+            if let Some(name) = &item_override.name {
+                item_row.item_name_translated = Some(name.clone());
+            }
+            if let Some(shop_name) = &item_override.shop_name {
+                item_row.author_name_translated = Some(shop_name.clone());
+            }
+            if let Some(description) = &item_override.description {
+                item_row.markdown_translated = Some(description.clone());
+            }
+        }
+    }
+
+    /// The fields of the item rows that have no override and no trustworthy machine translation.
+    ///
+    /// A machine translation is not trusted if it is missing,
+    /// the same as the original, or still mostly Japanese, Korean or Chinese.
+    #[must_use]
+    pub fn pending(&self, item_rows: &[ItemRow]) -> Vec<PendingTranslation> {
+        let mut pending = vec![];
+
+        for item_row in item_rows {
+            let item_override = self.items.get(&item_row.id).cloned().unwrap_or_default();
+
+            for (field, original, machine, done) in [
+                (
+                    Field::Name,
+                    &item_row.item_name,
+                    &item_row.item_name_translated,
+                    item_override.name.is_some(),
+                ),
+                (
+                    Field::ShopName,
+                    &item_row.author_name,
+                    &item_row.author_name_translated,
+                    item_override.shop_name.is_some(),
+                ),
+                (
+                    Field::Description,
+                    &item_row.markdown,
+                    &item_row.markdown_translated,
+                    item_override.description.is_some(),
+                ),
+            ] {
+                if done || !detect_language(original).needs_translation() {
+                    continue;
+                }
+
+                let trusted = machine.as_ref().is_some_and(|machine| {
+                    machine != original && !detect_language(machine).needs_translation()
+                });
+                if trusted {
+                    continue;
+                }
+
+                pending.push(PendingTranslation {
+                    id: item_row.id,
+                    field,
+                    original: original.clone(),
+                    machine: machine.clone(),
+                    translation: String::new(),
+                });
+            }
+        }
+
+        pending
+    }
+
+    /// Turn the filled in pending translations into overrides.
+    ///
+    /// Returns how many overrides were added or changed.
+    pub fn import(&mut self, pending: Vec<PendingTranslation>) -> usize {
+        let mut changed = 0;
+
+        for pending in pending {
+            let translation = pending.translation.trim();
+            if translation.is_empty() {
+                continue;
+            }
+
+            let field = self
+                .items
+                .entry(pending.id)
+                .or_default()
+                .field_mut(pending.field);
+            if field.as_deref() != Some(translation) {
+                *field = Some(translation.to_string());
+                changed += 1;
+            }
+        }
+
+        changed
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::item_row::ItemRow;
+
+/// BM25 term frequency saturation.
+const K1: f64 = 1.2;
+/// BM25 document length normalization.
+const B: f64 = 0.75;
+/// How many chars of context to show on each side of a match in a snippet.
+const SNIPPET_CONTEXT: usize = 30;
+
+/// Whether `c` is written without spaces between words, so it is indexed as bigrams.
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3040}'..='\u{30FF}'
+            | '\u{31F0}'..='\u{31FF}'
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FF66}'..='\u{FF9F}'
+            | '\u{AC00}'..='\u{D7AF}'
+    )
+}
+
+/// Split `text` into search tokens.
+///
+/// Latin text is split into lowercase words, CJK text into overlapping bigrams,
+/// since it has no spaces between words.
+///
+/// # Example
+/// ```
+/// use booth_archiver::models::search::tokenize;
+///
+/// assert_eq!(tokenize("桔梗 Hair"), vec!["桔梗", "hair"]);
+/// assert_eq!(tokenize("対応アバター"), vec!["対応", "応ア", "アバ", "バタ", "ター"]);
+/// assert_eq!(tokenize("髪"), vec!["髪"]);
+/// ```
+#[must_use]
+pub fn tokenize(text: &str) -> Vec<String> {
+    split(text, false)
+}
+
+/// The tokens `text` is indexed under: [`tokenize`], plus every CJK char on its own,
+/// so that a one char query like 髪 also finds 前髪.
+fn index_tokens(text: &str) -> Vec<String> {
+    split(text, true)
+}
+
+fn split(text: &str, cjk_unigrams: bool) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut word = String::new();
+    let mut cjk: Vec<char> = vec![];
+
+    let flush_cjk = |cjk: &mut Vec<char>, tokens: &mut Vec<String>| {
+        match cjk.len() {
+            0 => {}
+            1 => tokens.push(cjk[0].to_string()),
+            _ => {
+                if cjk_unigrams {
+                    tokens.extend(cjk.iter().map(char::to_string));
+                }
+                tokens.extend(cjk.windows(2).map(|pair| pair.iter().collect()));
+            }
+        }
+        cjk.clear();
+    };
+
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            cjk.push(c);
+        } else if c.is_alphanumeric() {
+            flush_cjk(&mut cjk, &mut tokens);
+            word.push(c);
+        } else {
+            flush_cjk(&mut cjk, &mut tokens);
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+        }
+    }
+    flush_cjk(&mut cjk, &mut tokens);
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+
+    tokens
+}
+
+/// An indexed item, with the texts snippets are taken from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Document {
+    id: u64,
+    /// field texts, most important first
+    texts: Vec<String>,
+    /// weighted number of tokens
+    len: f64,
+}
+
+/// A search result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub id: u64,
+    pub score: f64,
+    pub snippet: String,
+}
+
+/// Full text index over the names, shop names, tags and descriptions of item rows,
+/// both original and translated.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchIndex {
+    documents: Vec<Document>,
+    /// token -> (document index, weighted term frequency)
+    postings: HashMap<String, Vec<(usize, f64)>>,
+    avg_len: f64,
+}
+
+impl SearchIndex {
+    /// Index the given item rows.
+    #[must_use]
+    pub fn build(item_rows: &[ItemRow]) -> Self {
+        let mut index = Self::default();
+
+        for (doc, item_row) in item_rows.iter().enumerate() {
+            let fields = [
+                (Some(&item_row.item_name), 3.0),
+                (item_row.item_name_translated.as_ref(), 3.0),
+                (Some(&item_row.tags.join(" ")), 2.0),
+                (Some(&item_row.author_name), 2.0),
+                (item_row.author_name_translated.as_ref(), 2.0),
+                (Some(&item_row.markdown), 1.0),
+                (item_row.markdown_translated.as_ref(), 1.0),
+            ];
+
+            let mut frequencies: HashMap<String, f64> = HashMap::new();
+            let mut len = 0.0;
+            for (text, weight) in fields
+                .iter()
+                .filter_map(|(text, weight)| Some((text.as_ref()?, weight)))
+            {
+                for token in index_tokens(text) {
+                    *frequencies.entry(token).or_default() += weight;
+                    len += weight;
+                }
+            }
+
+            for (token, frequency) in frequencies {
+                index
+                    .postings
+                    .entry(token)
+                    .or_default()
+                    .push((doc, frequency));
+            }
+
+            index.documents.push(Document {
+                id: item_row.id,
+                texts: fields
+                    .iter()
+                    .filter_map(|(text, _)| text.cloned())
+                    .collect(),
+                len,
+            });
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let avg_len = index.documents.iter().map(|doc| doc.len).sum::<f64>()
+            / index.documents.len().max(1) as f64;
+        index.avg_len = avg_len;
+
+        index
+    }
+
+    /// Search the index, returning at most `limit` hits, best first.
+    #[must_use]
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let mut tokens = tokenize(query);
+        tokens.sort();
+        tokens.dedup();
+
+        #[allow(clippy::cast_precision_loss)]
+        let documents = self.documents.len() as f64;
+
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+        for token in &tokens {
+            let Some(postings) = self.postings.get(token) else {
+                continue;
+            };
+
+            #[allow(clippy::cast_precision_loss)]
+            let matching = postings.len() as f64;
+            let idf = ((documents - matching + 0.5) / (matching + 0.5)).ln_1p();
+
+            for (doc, frequency) in postings {
+                let len = self.documents[*doc].len / self.avg_len.max(f64::EPSILON);
+                let score = idf * frequency * (K1 + 1.0) / (frequency + K1 * (1.0 - B + B * len));
+                *scores.entry(*doc).or_default() += score;
+            }
+        }
+
+        let mut hits = scores.into_iter().collect::<Vec<_>>();
+        hits.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        hits.into_iter()
+            .take(limit)
+            .map(|(doc, score)| {
+                let document = &self.documents[doc];
+                SearchHit {
+                    id: document.id,
+                    score,
+                    snippet: snippet(&document.texts, &tokens),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The context around the first match of any token, from the first text that has one.
+fn snippet(texts: &[String], tokens: &[String]) -> String {
+    for text in texts {
+        let chars = text.chars().collect::<Vec<_>>();
+        let lower = chars
+            .iter()
+            .map(|c| c.to_lowercase().next().unwrap_or(*c))
+            .collect::<Vec<_>>();
+
+        let found = tokens.iter().find_map(|token| {
+            let token = token.chars().collect::<Vec<_>>();
+            lower
+                .windows(token.len())
+                .position(|window| window == token.as_slice())
+                .map(|start| (start, token.len()))
+        });
+
+        if let Some((start, len)) = found {
+            let from = start.saturating_sub(SNIPPET_CONTEXT);
+            let to = (start + len + SNIPPET_CONTEXT).min(chars.len());
+
+            let mut snippet = chars[from..to]
+                .iter()
+                .collect::<String>()
+                .replace('\n', " ");
+            if from > 0 {
+                snippet.insert(0, '…');
+            }
+            if to < chars.len() {
+                snippet.push('…');
+            }
+            return snippet;
+        }
+    }
+
+    texts.first().cloned().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: u64, item_name: &str) -> ItemRow {
+        ItemRow {
+            id,
+            item_name: item_name.to_string(),
+            ..ItemRow::default()
+        }
+    }
+
+    #[test]
+    fn one_kanji_queries_find_longer_words() {
+        let index = SearchIndex::build(&[item(1, "桔梗用 前髪セット"), item(2, "ワンピース服")]);
+
+        let ids = |query| {
+            index
+                .search(query, 10)
+                .iter()
+                .map(|hit| hit.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("髪"), vec![1]);
+        assert_eq!(ids("服"), vec![2]);
+        assert_eq!(ids("前髪"), vec![1]);
+        assert_eq!(index.search("髪", 10)[0].snippet, "桔梗用 前髪セット");
+    }
+}
@@ -0,0 +1,250 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::translation::is_url_char;
+
+/// Glyphs Booth descriptions use as bullets and heading decorations.
+const BULLETS: &[char] = &[
+    '■', '□', '◆', '◇', '●', '○', '◎', '・', '★', '☆', '※', '▶', '▷', '▼', '▽', '▲', '△', '【',
+    '】', '〓', '♪', '♡', '♥', '→', '←',
+];
+
+/// Markdown markers that only mean something at the start of a line.
+const LINE_MARKERS: &[&str] = &["- ", "* ", "+ ", "> "];
+
+/// Markdown markers that can appear anywhere in a line.
+const INLINE_MARKERS: &[&str] = &["**", "__", "~~", "`"];
+
+/// A piece of text, either to be translated or to be kept as is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Segment {
+    Text(String),
+    Protected(String),
+}
+
+/// Text split into the spans a translator should see and the ones it must not touch.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentedText {
+    segments: Vec<Segment>,
+}
+
+impl SegmentedText {
+    #[must_use]
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// The spans to send to the translator, in order.
+    #[must_use]
+    pub fn translatable(&self) -> Vec<String> {
+        self.segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Text(text) => Some(text.clone()),
+                Segment::Protected(_) => None,
+            })
+            .collect()
+    }
+
+    /// Put the text back together with `translations` in place of the translatable spans.
+    ///
+    /// Spans without a translation keep their original text.
+    #[must_use]
+    pub fn reassemble(&self, translations: &[String]) -> String {
+        let mut translations = translations.iter();
+
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => translations.next().unwrap_or(text).as_str(),
+                Segment::Protected(text) => text.as_str(),
+            })
+            .collect()
+    }
+}
+
+/// Splits text into translatable and protected spans.
+///
+/// URLs, emoji, markdown syntax, bullet glyphs, hashtags and the protected words
+/// (e.g. avatar names) are protected, as is the whitespace around them,
+/// so only actual sentences are sent to the translator.
+///
+/// # Example
+/// ```
+/// use booth_archiver::models::segmenter::Segmenter;
+///
+/// let segmenter = Segmenter::new(vec!["Kikyo".to_string()]);
+/// let text = "■ 対応アバター: Kikyo 🐱\n- 詳細は https://example.com/a?b=c をご覧ください #VRChat";
+/// let segmented = segmenter.segment(text);
+///
+/// assert_eq!(
+///     segmented.translatable(),
+///     vec!["対応アバター:", "詳細は", "をご覧ください"]
+/// );
+/// assert_eq!(segmented.reassemble(&segmented.translatable()), text);
+/// assert_eq!(
+///     segmented.reassemble(&[
+///         "Supported avatars:".to_string(),
+///         "For details see".to_string(),
+///         "please".to_string(),
+///     ]),
+///     "■ Supported avatars: Kikyo 🐱\n- For details see https://example.com/a?b=c please #VRChat"
+/// );
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Segmenter {
+    /// longest word first, so the longest match wins
+    protected_words: Vec<String>,
+}
+
+impl Segmenter {
+    #[must_use]
+    pub fn new(mut protected_words: Vec<String>) -> Self {
+        protected_words.retain(|word| !word.is_empty());
+        protected_words.sort_by_key(|word| std::cmp::Reverse(word.len()));
+
+        Self { protected_words }
+    }
+
+    /// Split `text` into translatable and protected spans.
+    #[must_use]
+    pub fn segment(&self, text: &str) -> SegmentedText {
+        let mut segments = vec![];
+        let mut text_start = 0;
+        let mut idx = 0;
+        let mut prev = None;
+
+        while idx < text.len() {
+            let rest = &text[idx..];
+            let line_start = prev.is_none_or(|prev| prev == '\n');
+
+            match self.protected_len(rest, prev, line_start) {
+                0 => {
+                    let c = rest.chars().next().unwrap();
+                    idx += c.len_utf8();
+                    prev = Some(c);
+                }
+                len => {
+                    if text_start < idx {
+                        segments.push(Segment::Text(text[text_start..idx].to_string()));
+                    }
+                    segments.push(Segment::Protected(rest[..len].to_string()));
+                    idx += len;
+                    text_start = idx;
+                    prev = rest[..len].chars().last();
+                }
+            }
+        }
+        if text_start < text.len() {
+            segments.push(Segment::Text(text[text_start..].to_string()));
+        }
+
+        SegmentedText {
+            segments: tidy(segments),
+        }
+    }
+
+    /// Length in bytes of the protected span at the start of `rest`, or 0 if there is none.
+    fn protected_len(&self, rest: &str, prev: Option<char>, line_start: bool) -> usize {
+        let first = rest.chars().next().unwrap_or_default();
+
+        if rest.starts_with("http://") || rest.starts_with("https://") {
+            return rest.find(|c| !is_url_char(c)).unwrap_or(rest.len());
+        }
+
+        if line_start {
+            let heading = rest.chars().take_while(|c| *c == '#').count();
+            if heading > 0 && rest[heading..].starts_with(' ') {
+                return heading + 1;
+            }
+
+            if let Some(marker) = LINE_MARKERS
+                .iter()
+                .find(|marker| rest.starts_with(**marker))
+            {
+                return marker.len();
+            }
+
+            let digits = rest.chars().take_while(char::is_ascii_digit).count();
+            if digits > 0 && rest[digits..].starts_with(". ") {
+                return digits + 2;
+            }
+        }
+
+        if first == '#' || first == '＃' {
+            let tag = rest[first.len_utf8()..]
+                .find(|c: char| c.is_whitespace() || c == '#' || c == '＃')
+                .unwrap_or(rest.len() - first.len_utf8());
+            if tag > 0 {
+                return first.len_utf8() + tag;
+            }
+        }
+
+        if let Some(marker) = INLINE_MARKERS
+            .iter()
+            .find(|marker| rest.starts_with(**marker))
+        {
+            return marker.len();
+        }
+
+        if BULLETS.contains(&first) || is_emoji(first) {
+            return first.len_utf8();
+        }
+
+        let word_boundary = |c: Option<char>| c.is_none_or(|c| !c.is_ascii_alphanumeric());
+        if word_boundary(prev) {
+            if let Some(word) = self.protected_words.iter().find(|word| {
+                rest.starts_with(word.as_str()) && word_boundary(rest[word.len()..].chars().next())
+            }) {
+                return word.len();
+            }
+        }
+
+        0
+    }
+}
+
+/// Whether `c` is part of an emoji, including the joiners and modifiers between emoji.
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1F000}'..='\u{1FAFF}'
+            | '\u{2600}'..='\u{27BF}'
+            | '\u{2B00}'..='\u{2BFF}'
+            | '\u{FE0E}'..='\u{FE0F}'
+            | '\u{200D}'
+            | '\u{20E3}'
+            | '\u{E0020}'..='\u{E007F}'
+    )
+}
+
+/// Move the whitespace around text spans into the protected spans,
+/// protect spans with nothing to translate, and merge neighbouring protected spans.
+fn tidy(segments: Vec<Segment>) -> Vec<Segment> {
+    let mut tidied: Vec<Segment> = vec![];
+    let push_protected = |tidied: &mut Vec<Segment>, text: &str| {
+        if text.is_empty() {
+            return;
+        }
+        match tidied.last_mut() {
+            Some(Segment::Protected(prev)) => prev.push_str(text),
+            _ => tidied.push(Segment::Protected(text.to_string())),
+        }
+    };
+
+    for segment in segments {
+        match segment {
+            Segment::Text(text) if text.chars().any(char::is_alphabetic) => {
+                let trimmed_start = text.trim_start();
+                let trimmed = trimmed_start.trim_end();
+                push_protected(&mut tidied, &text[..text.len() - trimmed_start.len()]);
+                tidied.push(Segment::Text(trimmed.to_string()));
+                push_protected(&mut tidied, &trimmed_start[trimmed.len()..]);
+            }
+            Segment::Text(text) | Segment::Protected(text) => {
+                push_protected(&mut tidied, &text);
+            }
+        }
+    }
+
+    tidied
+}
@@ -0,0 +1,92 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use indicatif::ParallelProgressIterator;
+use rayon::prelude::*;
+
+use crate::models::item_row::ItemRow;
+use crate::models::web_client::WebScraper;
+use crate::zaphkiel::backend::write_atomic;
+use crate::zaphkiel::utils::get_pb;
+
+/// Where the thumbnail at `url` is cached inside `dir`.
+#[must_use]
+pub fn thumbnail_path(dir: &Path, url: &str) -> PathBuf {
+    let name = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .replace(['/', '?', '&', '=', ':'], "_");
+
+    dir.join(name)
+}
+
+/// Download the thumbnail of every item into `dir`, skipping the ones already downloaded.
+///
+/// Returns the local path of each item's thumbnail, in the same order as `items`,
+/// or `None` if the item has no images or the download failed.
+pub fn download_thumbnails(
+    client: &WebScraper,
+    items: &[ItemRow],
+    dir: &Path,
+) -> Vec<Option<PathBuf>> {
+    fs::create_dir_all(dir)
+        .unwrap_or_else(|e| panic!("failed to create `{}` because of error: {e}", dir.display()));
+
+    items
+        .par_iter()
+        .progress_with(get_pb(items.len() as u64, "downloading Thumbnails"))
+        .map(|item| {
+            let url = item.thumbnail_url.as_ref()?;
+            let path = thumbnail_path(dir, url);
+
+            if fs::metadata(&path).is_ok() {
+                return Some(path);
+            }
+
+            match client.get_bytes(url) {
+                Ok(bytes) => {
+                    // a thumbnail that exists counts as downloaded, so it must never be partial
+                    write_atomic(&path, &bytes);
+                    Some(path)
+                }
+                Err(err) => {
+                    println!("failed to download thumbnail {url}: {err}");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{Response, StubServer, TempDir};
+
+    #[test]
+    fn downloads_once_without_leaving_temporary_files() {
+        let dir = TempDir::new("thumbnails");
+        let server = StubServer::serve(vec![Response::new(200, "image")]);
+        let client = WebScraper::new("session".to_string(), true);
+        let items = [
+            ItemRow {
+                thumbnail_url: Some(format!("{}/thumb.jpg", server.url)),
+                ..ItemRow::default()
+            },
+            ItemRow::default(),
+        ];
+
+        let paths = download_thumbnails(&client, &items, &dir.join("thumbnails"));
+        assert_eq!(server.requests().len(), 1);
+        let path = paths[0].clone().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"image");
+        assert_eq!(paths[1], None);
+        assert_eq!(fs::read_dir(dir.join("thumbnails")).unwrap().count(), 1);
+
+        // already downloaded, the stub server is gone so a request would fail
+        assert_eq!(
+            download_thumbnails(&client, &items[..1], &dir.join("thumbnails")),
+            [Some(path)]
+        );
+    }
+}
@@ -8,15 +8,11 @@ pub enum TranslationError {
     HttpErr(String),
     UrlParseErr,
     JsonParseErr(String),
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct UrlTranslationCTX {
-    original_text: String,
-    left: String,
-    url: String,
-    right: String,
-    tled: String,
+    /// the translator answered with a different number of translations than it was sent texts
+    CountMismatch {
+        sent: usize,
+        received: usize,
+    },
 }
 
 #[inline]
@@ -38,26 +34,8 @@ pub fn decode(text: impl AsRef<str>) -> String {
 #[inline]
 #[must_use]
 pub fn handle_http(text: &str) -> (String, String, String) {
-    let (left, right) = text.split_once("http").unwrap_or((&text, ""));
-    let pos = right
-        .find(|c: char| !{
-            c == '&'
-                || c == '$'
-                || c == '+'
-                || c == '.'
-                || c == ','
-                || c == '/'
-                || c == ':'
-                || c == ';'
-                || c == '='
-                || c == '_'
-                || c == '?'
-                || c == '@'
-                || c == '#'
-                || c == '-'
-                || c.is_alphanumeric()
-        })
-        .unwrap_or(right.len());
+    let (left, right) = text.split_once("http").unwrap_or((text, ""));
+    let pos = right.find(|c: char| !is_url_char(c)).unwrap_or(right.len());
     let (url, right) = right.split_at(pos);
     let url = format!("http{url}");
 
@@ -65,3 +43,27 @@ pub fn handle_http(text: &str) -> (String, String, String) {
 
     (left, url, right)
 }
+
+/// Whether `c` can be part of a url.
+#[inline]
+#[must_use]
+pub fn is_url_char(c: char) -> bool {
+    matches!(
+        c,
+        '&' | '$'
+            | '+'
+            | '.'
+            | ','
+            | '/'
+            | ':'
+            | ';'
+            | '='
+            | '_'
+            | '?'
+            | '@'
+            | '#'
+            | '-'
+            | '%'
+            | '~'
+    ) || c.is_ascii_alphanumeric()
+}
@@ -0,0 +1,581 @@
+use std::collections::{BTreeSet, HashMap};
+
+use indicatif::ParallelProgressIterator;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use ureq::{Agent, AgentBuilder};
+
+use crate::models::glossary::Glossaries;
+use crate::models::item_row::ItemRow;
+use crate::models::language::{detect_language, Language};
+use crate::models::segmenter::{SegmentedText, Segmenter};
+use crate::models::translation::{decode, encode, TranslationError};
+use crate::zaphkiel::cache::Cache;
+use crate::zaphkiel::utils::get_pb;
+
+/// Something that can translate text from one language to another.
+pub trait Translator: Send + Sync {
+    /// Translate every text in `texts` from the `source` to the `target` language.
+    ///
+    /// The translations are returned in the same order as `texts`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the translation service could not be reached
+    /// or its response could not be understood.
+    fn translate(
+        &self,
+        texts: &[String],
+        source: &str,
+        target: &str,
+    ) -> Result<Vec<String>, TranslationError>;
+
+    /// How much can be sent to the translator in one request.
+    fn batch_limits(&self) -> BatchLimits {
+        BatchLimits::default()
+    }
+}
+
+/// Limits of a single translation request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchLimits {
+    /// most texts in one request
+    pub max_texts: usize,
+    /// most characters, summed over all texts in one request
+    pub max_chars: usize,
+}
+
+impl Default for BatchLimits {
+    fn default() -> Self {
+        Self {
+            max_texts: 50,
+            max_chars: 5_000,
+        }
+    }
+}
+
+/// Which translation service to use, and how to reach it.
+///
+/// # Example
+/// ```
+/// use booth_archiver::models::translator::TranslatorConfig;
+///
+/// let config: TranslatorConfig =
+///     ron::from_str(r#"DeepL(url: "https://api-free.deepl.com", api_key: "secret")"#).unwrap();
+/// let translator = config.build();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TranslatorConfig {
+    /// A LibreTranslate compatible API, e.g. `http://localhost:5000`.
+    LibreTranslate {
+        url: String,
+        api_key: Option<String>,
+    },
+    /// A DeepL compatible API, e.g. `https://api-free.deepl.com`.
+    DeepL { url: String, api_key: String },
+}
+
+impl TranslatorConfig {
+    /// Create the translator described by this config.
+    #[must_use]
+    pub fn build(&self) -> Box<dyn Translator> {
+        match self.clone() {
+            Self::LibreTranslate { url, api_key } => Box::new(LibreTranslate::new(url, api_key)),
+            Self::DeepL { url, api_key } => Box::new(DeepL::new(url, api_key)),
+        }
+    }
+}
+
+/// Translator for the `/translate` endpoint of LibreTranslate compatible APIs.
+#[derive(Debug)]
+pub struct LibreTranslate {
+    client: Agent,
+    url: String,
+    api_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LibreTranslateResponse {
+    #[serde(rename = "translatedText")]
+    translated_text: Vec<String>,
+}
+
+impl LibreTranslate {
+    /// Create a new LibreTranslate translator.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The base url of the API, without the `/translate` path.
+    /// * `api_key` - The API key, if the instance requires one.
+    #[must_use]
+    pub fn new(url: String, api_key: Option<String>) -> Self {
+        Self {
+            client: AgentBuilder::new().build(),
+            url: url.trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+}
+
+impl Translator for LibreTranslate {
+    fn translate(
+        &self,
+        texts: &[String],
+        source: &str,
+        target: &str,
+    ) -> Result<Vec<String>, TranslationError> {
+        let res = self
+            .client
+            .post(&format!("{}/translate", self.url))
+            .send_json(json!({
+                "q": texts,
+                "source": source,
+                "target": target,
+                "format": "text",
+                "api_key": self.api_key,
+            }))
+            .map_err(|e| TranslationError::HttpErr(e.to_string()))?
+            .into_json::<LibreTranslateResponse>()
+            .map_err(|e| TranslationError::JsonParseErr(e.to_string()))?;
+
+        Ok(res.translated_text)
+    }
+
+    fn batch_limits(&self) -> BatchLimits {
+        // the default `--char-limit` of a LibreTranslate instance
+        BatchLimits {
+            max_texts: 100,
+            max_chars: 5_000,
+        }
+    }
+}
+
+/// Translator for the `/v2/translate` endpoint of DeepL compatible APIs.
+#[derive(Debug)]
+pub struct DeepL {
+    client: Agent,
+    url: String,
+    api_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeepLResponse {
+    translations: Vec<DeepLTranslation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeepLTranslation {
+    text: String,
+}
+
+impl DeepL {
+    /// Create a new DeepL translator.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The base url of the API, without the `/v2/translate` path.
+    /// * `api_key` - The API key.
+    #[must_use]
+    pub fn new(url: String, api_key: String) -> Self {
+        Self {
+            client: AgentBuilder::new().build(),
+            url: url.trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+}
+
+impl Translator for DeepL {
+    fn translate(
+        &self,
+        texts: &[String],
+        source: &str,
+        target: &str,
+    ) -> Result<Vec<String>, TranslationError> {
+        let res = self
+            .client
+            .post(&format!("{}/v2/translate", self.url))
+            .set("Authorization", &format!("DeepL-Auth-Key {}", self.api_key))
+            .send_json(json!({
+                "text": texts,
+                "source_lang": source.to_uppercase(),
+                "target_lang": target.to_uppercase(),
+            }))
+            .map_err(|e| TranslationError::HttpErr(e.to_string()))?
+            .into_json::<DeepLResponse>()
+            .map_err(|e| TranslationError::JsonParseErr(e.to_string()))?;
+
+        Ok(res
+            .translations
+            .into_iter()
+            .map(|translation| translation.text)
+            .collect())
+    }
+
+    fn batch_limits(&self) -> BatchLimits {
+        // DeepL takes up to 50 texts and 128 KiB per request, leave room for multi byte chars
+        BatchLimits {
+            max_texts: 50,
+            max_chars: 30_000,
+        }
+    }
+}
+
+/// Split `texts` into batches that stay within the translator's limits.
+///
+/// A text longer than the character budget on its own gets a batch to itself.
+///
+/// # Example
+/// ```
+/// use booth_archiver::models::translator::{batches, BatchLimits};
+///
+/// let limits = BatchLimits { max_texts: 2, max_chars: 5 };
+/// let texts = |texts: &[&str]| texts.iter().map(ToString::to_string).collect::<Vec<_>>();
+///
+/// assert!(batches(vec![], limits).is_empty());
+///
+/// // a text over the character budget still goes out, alone
+/// assert_eq!(
+///     batches(texts(&["a", "toolong", "b"]), limits),
+///     [texts(&["a"]), texts(&["toolong"]), texts(&["b"])]
+/// );
+///
+/// // exactly `max_texts` texts fit in one batch, one more starts the next
+/// assert_eq!(batches(texts(&["a", "b"]), limits), [texts(&["a", "b"])]);
+/// assert_eq!(
+///     batches(texts(&["a", "b", "c"]), limits),
+///     [texts(&["a", "b"]), texts(&["c"])]
+/// );
+///
+/// // exactly `max_chars` characters fit too
+/// assert_eq!(batches(texts(&["ab", "cde", "f"]), limits), [texts(&["ab", "cde"]), texts(&["f"])]);
+/// ```
+#[must_use]
+pub fn batches(texts: Vec<String>, limits: BatchLimits) -> Vec<Vec<String>> {
+    let mut batches = vec![];
+    let mut batch: Vec<String> = vec![];
+    let mut chars = 0;
+
+    for text in texts {
+        let len = text.chars().count();
+        if !batch.is_empty() && (batch.len() >= limits.max_texts || chars + len > limits.max_chars)
+        {
+            batches.push(std::mem::take(&mut batch));
+            chars = 0;
+        }
+        chars += len;
+        batch.push(text);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+
+    batches
+}
+
+/// The translation cache key of the encoded `text`, translations are only reused
+/// for the same language pair.
+fn cache_key(text: &str, source: &str, target: &str) -> String {
+    format!("{source}>{target}\t{text}")
+}
+
+/// Translate the `texts` that are not in the translation cache yet and add them to it.
+///
+/// The texts are normalized with [`encode`] and deduplicated first,
+/// so every distinct text is only ever translated once,
+/// then sent to the translator in batches.
+///
+/// Returns the errors of the batches that failed, their texts stay out of the cache.
+pub fn fill_cache(
+    translator: &dyn Translator,
+    cache: &Cache,
+    texts: &[String],
+    source: &str,
+    target: &str,
+) -> Vec<TranslationError> {
+    let missing = texts
+        .iter()
+        .map(encode)
+        .filter(|key| !key.is_empty())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|key| cache.get(&cache_key(key, source, target)).is_none())
+        .collect::<Vec<_>>();
+
+    let batches = batches(missing, translator.batch_limits());
+
+    batches
+        .par_iter()
+        .progress_with(get_pb(batches.len() as u64, "translating Batches"))
+        .filter_map(|batch| {
+            let translated = match translator.translate(batch, source, target) {
+                Ok(translated) if translated.len() == batch.len() => translated,
+                Ok(translated) => {
+                    return Some(TranslationError::CountMismatch {
+                        sent: batch.len(),
+                        received: translated.len(),
+                    })
+                }
+                Err(err) => return Some(err),
+            };
+
+            for (key, translation) in batch.iter().zip(&translated) {
+                cache.add(cache_key(key, source, target), encode(translation));
+            }
+
+            None
+        })
+        .collect()
+}
+
+/// The cached translation of `text` from `source` to `target`, if there is one.
+///
+/// # Example
+/// ```
+/// use booth_archiver::models::translation::TranslationError;
+/// use booth_archiver::models::translator::{cached_translation, fill_cache, Translator};
+/// use booth_archiver::zaphkiel::cache::Cache;
+///
+/// /// tags every text with the language it was translated to
+/// struct Tagger;
+///
+/// impl Translator for Tagger {
+///     fn translate(
+///         &self,
+///         texts: &[String],
+///         _source: &str,
+///         target: &str,
+///     ) -> Result<Vec<String>, TranslationError> {
+///         Ok(texts.iter().map(|text| format!("{target}:{text}")).collect())
+///     }
+/// }
+///
+/// let cache: Cache = Cache::default();
+/// let texts = ["猫".to_string()];
+/// assert!(fill_cache(&Tagger, &cache, &texts, "ja", "en").is_empty());
+///
+/// assert_eq!(cached_translation(&cache, "猫", "ja", "en"), Some("en:猫".to_string()));
+/// assert_eq!(cached_translation(&cache, "猫", "ja", "de"), None);
+/// assert_eq!(cached_translation(&cache, "猫", "zh", "en"), None);
+/// ```
+#[must_use]
+pub fn cached_translation(cache: &Cache, text: &str, source: &str, target: &str) -> Option<String> {
+    let key = encode(text);
+    if key.is_empty() {
+        return Some(text.to_string());
+    }
+
+    cache.get(&cache_key(&key, source, target)).map(decode)
+}
+
+/// Everything needed to translate the texts of the item rows.
+///
+/// Texts go through the shop's glossary first, the rest is split by the segmenter
+/// and only the translatable spans in Japanese, Korean or Chinese are sent to the translator,
+/// if there is one, with their detected language as the source language.
+pub struct TranslationPipeline<'a> {
+    pub translator: Option<&'a dyn Translator>,
+    pub glossaries: &'a Glossaries,
+    pub segmenter: &'a Segmenter,
+    pub cache: &'a Cache,
+    pub target: &'a str,
+}
+
+/// How one text of an item row gets translated.
+enum Plan {
+    /// the whole text is in the glossary
+    Glossary(String),
+    /// the text's translatable spans need translating
+    Segmented(SegmentedText),
+}
+
+impl TranslationPipeline<'_> {
+    /// The texts of an item row to translate: item name, author name and each markdown line.
+    fn texts(item_row: &ItemRow) -> Vec<String> {
+        let mut texts = vec![item_row.item_name.clone(), item_row.author_name.clone()];
+        texts.extend(item_row.markdown.split('\n').map(ToString::to_string));
+
+        texts
+    }
+
+    /// Texts that are in the glossary as a whole are translated by it directly,
+    /// the rest have their glossary phrases substituted and are segmented.
+    fn plan(&self, item_row: &ItemRow) -> Vec<Plan> {
+        let glossary = self.glossaries.for_shop(&item_row.author_link);
+
+        Self::texts(item_row)
+            .iter()
+            .map(|text| {
+                glossary.exact(text).map_or_else(
+                    || Plan::Segmented(self.segmenter.segment(&glossary.substitute(text))),
+                    Plan::Glossary,
+                )
+            })
+            .collect()
+    }
+
+    /// Put the translations of the planned texts together,
+    /// or `None` if a span that needed translating has no translation.
+    fn assemble(&self, plans: &[Plan]) -> Option<Vec<String>> {
+        plans
+            .iter()
+            .map(|plan| match plan {
+                Plan::Glossary(translated) => Some(translated.clone()),
+                Plan::Segmented(segmented) => {
+                    let spans = segmented
+                        .translatable()
+                        .into_iter()
+                        .map(|span| self.translate_span(span))
+                        .collect::<Option<Vec<_>>>()?;
+                    Some(segmented.reassemble(&spans))
+                }
+            })
+            .collect()
+    }
+
+    /// The translation of a single span, the span itself if it doesn't need translating.
+    fn translate_span(&self, span: String) -> Option<String> {
+        // only the languages that need translating have a code
+        let Some(source) = detect_language(&span).code() else {
+            return Some(span);
+        };
+        if self.translator.is_none() {
+            return Some(span);
+        }
+
+        cached_translation(self.cache, &span, source, self.target)
+    }
+
+    /// Fill in the translated item name, author name and markdown of every item row.
+    ///
+    /// The markdown is translated line by line.
+    /// The spans of all item rows are collected and deduplicated first,
+    /// and sent to the translator grouped by language, in batches.
+    /// Without a translator only the glossary of the item's shop is applied.
+    ///
+    /// Returns the errors of the batches that could not be translated,
+    /// the item rows they belong to keep their translated fields as `None`.
+    pub fn translate_item_rows(&self, item_rows: &mut [ItemRow]) -> Vec<TranslationError> {
+        let plans = item_rows
+            .par_iter()
+            .map(|item_row| self.plan(item_row))
+            .collect::<Vec<_>>();
+
+        let mut errs = vec![];
+
+        if let Some(translator) = self.translator {
+            let mut by_language: HashMap<Language, Vec<String>> = HashMap::new();
+            for plan in plans.iter().flatten() {
+                if let Plan::Segmented(segmented) = plan {
+                    for span in segmented.translatable() {
+                        let language = detect_language(&span);
+                        if language.needs_translation() {
+                            by_language.entry(language).or_default().push(span);
+                        }
+                    }
+                }
+            }
+
+            for (language, spans) in by_language {
+                let source = language.code().unwrap();
+                errs.extend(fill_cache(
+                    translator,
+                    self.cache,
+                    &spans,
+                    source,
+                    self.target,
+                ));
+            }
+        }
+
+        let len = item_rows.len() as u64;
+        item_rows
+            .par_iter_mut()
+            .zip(plans)
+            .progress_with(get_pb(len, "translating Item Rows"))
+            .for_each(|(item_row, plans)| {
+                if let Some(mut translated) = self.assemble(&plans) {
+                    let markdown = translated.split_off(2);
+                    item_row.author_name_translated = translated.pop();
+                    item_row.item_name_translated = translated.pop();
+                    item_row.markdown_translated = Some(markdown.join("\n"));
+                }
+            });
+
+        errs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_utils::{Response, StubServer};
+
+    #[test]
+    fn backends_send_and_parse_their_api_format() {
+        let server = StubServer::serve(vec![
+            Response::json(r#"{"translatedText":["hello"]}"#),
+            Response::json(r#"{"translations":[{"text":"hello"}]}"#),
+        ]);
+        let texts = ["こんにちは".to_string()];
+
+        let libre = TranslatorConfig::LibreTranslate {
+            url: server.url.clone(),
+            api_key: Some("key".into()),
+        };
+        assert_eq!(
+            libre.build().translate(&texts, "ja", "en").unwrap(),
+            ["hello"]
+        );
+
+        let deepl = TranslatorConfig::DeepL {
+            url: server.url.clone(),
+            api_key: "secret".into(),
+        };
+        assert_eq!(
+            deepl.build().translate(&texts, "ja", "en").unwrap(),
+            ["hello"]
+        );
+
+        let [libre, deepl] = <[_; 2]>::try_from(server.requests()).ok().unwrap();
+        assert!(libre.head.starts_with("POST /translate "));
+        assert_eq!(
+            libre.json(),
+            json!({"q": texts, "source": "ja", "target": "en", "format": "text", "api_key": "key"})
+        );
+        assert!(deepl.head.starts_with("POST /v2/translate "));
+        assert_eq!(deepl.header("Authorization"), Some("DeepL-Auth-Key secret"));
+        assert_eq!(
+            deepl.json(),
+            json!({"text": texts, "source_lang": "JA", "target_lang": "EN"})
+        );
+    }
+
+    #[test]
+    fn count_mismatch_keeps_the_batch_out_of_the_cache() {
+        let server = StubServer::serve(vec![Response::json(
+            r#"{"translatedText":["hello","world"]}"#,
+        )]);
+        let libre = TranslatorConfig::LibreTranslate {
+            url: server.url.clone(),
+            api_key: None,
+        };
+        let texts = ["こんにちは".to_string()];
+
+        let cache: Cache = Cache::default();
+        let errs = fill_cache(libre.build().as_ref(), &cache, &texts, "ja", "en");
+
+        assert_eq!(
+            errs,
+            [TranslationError::CountMismatch {
+                sent: 1,
+                received: 2
+            }]
+        );
+        assert_eq!(cache.get_stats().cache_size, 0);
+        server.requests();
+    }
+}
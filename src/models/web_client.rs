@@ -1,12 +1,30 @@
-use std::sync::{Arc, RwLock};
+use std::io::{self, ErrorKind, Read};
+use std::sync::Arc;
 
 use indicatif::ParallelProgressIterator;
 use rayon::prelude::*;
 use ureq::{Agent, AgentBuilder};
 
-use crate::zaphkiel::cache::Cache;
+use crate::zaphkiel::cache::{Cache, EntryMeta};
+use crate::zaphkiel::codec::{Decode, Encode};
 use crate::zaphkiel::utils::get_pb;
 
+const NOT_MODIFIED: u16 = 304;
+
+/// Whether `url` goes to `booth.pm` or one of its subdomains.
+fn is_booth_url(url: &str) -> bool {
+    let Some((_, rest)) = url.split_once("://") else {
+        return false;
+    };
+    let host = rest
+        .split(['/', '?', '#', ':'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    host == "booth.pm" || host.ends_with(".booth.pm")
+}
+
 /// Basic web scraper that uses a cache to avoid downloading the same page twice.
 #[derive(Debug)]
 pub struct WebScraper {
@@ -39,41 +57,101 @@ impl WebScraper {
 }
 
 impl WebScraper {
-    /// Get a single page.
+    /// A GET request to `url`, with the session cookie only if it goes to booth,
+    /// so it never leaks to e.g. the image CDN.
+    fn get(&self, url: &str) -> ureq::Request {
+        let request = self.client.get(url);
+
+        if is_booth_url(url) {
+            request.set("Cookie", &self.cookie)
+        } else {
+            request
+        }
+    }
+
+    /// Get a single page, decoded as a `V`, e.g. a `String` for the raw body
+    /// or [`Json`](crate::zaphkiel::codec::Json) to parse it.
+    ///
+    /// Fresh cached pages are returned as is, expired ones are revalidated with the
+    /// `ETag`/`Last-Modified` they were fetched with, a `304 Not Modified` counts as a hit.
+    /// Bodies that can't be decoded are an error and are not cached.
     #[allow(clippy::result_large_err)]
     #[inline]
-    pub fn get_one(
+    pub fn get_one<V: Encode + Decode + Clone>(
         &self,
         url: String,
-        cache: Option<Arc<RwLock<Cache>>>,
-    ) -> Result<String, ureq::Error> {
-        if let Some(cache) = cache.clone() {
-            if let Some(html) = cache.clone().read().unwrap().get(&url) {
+        cache: Option<Arc<Cache<String, V>>>,
+    ) -> Result<V, ureq::Error> {
+        let cached = cache.as_ref().and_then(|cache| cache.peek(&url));
+
+        if let (Some(cache), Some((html, _))) = (&cache, &cached) {
+            if cache.is_fresh(&url) {
+                cache.record_hit(&url);
+                return Ok(html.clone());
+            }
+        }
+
+        let mut request = self.get(&url);
+        if let Some((_, Some(meta))) = &cached {
+            if let Some(etag) = &meta.etag {
+                request = request.set("If-None-Match", etag);
+            }
+            if let Some(last_modified) = &meta.last_modified {
+                request = request.set("If-Modified-Since", last_modified);
+            }
+        }
+
+        let response = request.call()?;
+
+        if response.status() == NOT_MODIFIED {
+            if let (Some(cache), Some((html, _))) = (&cache, cached) {
+                cache.revalidate(&url);
                 return Ok(html);
             }
         }
 
-        let res = self
-            .client
-            .get(&url)
-            .set("Cookie", &self.cookie.clone())
-            .call()?
-            .into_string()?;
+        let meta = EntryMeta {
+            status: Some(response.status()),
+            etag: response.header("ETag").map(ToString::to_string),
+            last_modified: response.header("Last-Modified").map(ToString::to_string),
+            ..EntryMeta::now()
+        };
+        let res = V::decode_owned(response.into_string()?).map_err(|e| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("failed to decode `{url}`: {e}"),
+            )
+        })?;
 
-        if let Some(cache) = cache.clone() {
-            cache.clone().write().unwrap().add(url, res.clone());
+        if let Some(cache) = cache {
+            cache.record_miss(&url);
+            cache.add_with_meta(url, res.clone(), meta);
         }
 
         Ok(res)
     }
 
+    /// Get the raw bytes of a single file, bypassing the cache.
+    #[allow(clippy::result_large_err)]
+    pub fn get_bytes(&self, url: &str) -> Result<Vec<u8>, ureq::Error> {
+        let mut bytes = vec![];
+
+        self.get(url)
+            .call()?
+            .into_reader()
+            .read_to_end(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
     /// Get multiple pages, in parallel.
-    pub fn get_many(
+    #[allow(clippy::result_large_err)]
+    pub fn get_many<V: Encode + Decode + Clone + Send + Sync>(
         &self,
         urls: Vec<String>,
-        cache: Arc<RwLock<Cache>>,
+        cache: Arc<Cache<String, V>>,
         msg: &'static str,
-    ) -> Vec<Result<String, ureq::Error>> {
+    ) -> Vec<Result<V, ureq::Error>> {
         let htmls = urls
             .par_iter()
             .progress_with(get_pb(urls.len() as u64, msg))
@@ -87,3 +165,104 @@ impl WebScraper {
         todo!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_utils::{Response, StubServer};
+
+    /// A cache with a one hour ttl, holding `url` as fetched a day ago.
+    fn expired_cache(url: &str) -> Arc<Cache<String, String>> {
+        let cache = Cache::default().with_ttl(Some(Duration::from_secs(60 * 60)));
+        cache.add_with_meta(
+            url.to_string(),
+            "cached body".to_string(),
+            EntryMeta {
+                fetched_at: EntryMeta::now().fetched_at - 24 * 60 * 60,
+                status: Some(200),
+                etag: Some("\"v1\"".to_string()),
+                last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".to_string()),
+            },
+        );
+        Arc::new(cache)
+    }
+
+    #[test]
+    fn only_booth_urls_get_the_cookie() {
+        assert!(is_booth_url("https://booth.pm/en/items/1.json"));
+        assert!(is_booth_url(
+            "https://accounts.booth.pm/wish_list_name_items.json?page=1"
+        ));
+        assert!(!is_booth_url("https://booth.pximg.net/c/620x620/thumb.jpg"));
+        assert!(!is_booth_url("https://notbooth.pm/"));
+        assert!(!is_booth_url("https://booth.pm.example.com/"));
+        assert!(!is_booth_url("booth.pm"));
+
+        let server = StubServer::serve(vec![Response::new(200, "image")]);
+        let client = WebScraper::new("session".to_string(), true);
+        let bytes = client
+            .get_bytes(&format!("{}/thumb.jpg", server.url))
+            .unwrap();
+
+        assert_eq!(bytes, b"image");
+        assert_eq!(server.requests()[0].header("Cookie"), None);
+    }
+
+    #[test]
+    fn not_modified_refreshes_the_cached_page() {
+        let server = StubServer::serve(vec![Response::new(NOT_MODIFIED, "")]);
+        let url = format!("{}/items/1.json", server.url);
+        let cache = expired_cache(&url);
+        assert!(!cache.is_fresh(&url));
+
+        let client = WebScraper::new("session".to_string(), true);
+        let body = client.get_one(url.clone(), Some(cache.clone())).unwrap();
+
+        let request = &server.requests()[0];
+        assert_eq!(request.header("If-None-Match"), Some("\"v1\""));
+        assert_eq!(
+            request.header("If-Modified-Since"),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+        assert_eq!(body, "cached body");
+        assert!(cache.is_fresh(&url));
+        let meta = cache.get_meta(&url).unwrap();
+        assert!(meta.age() < Duration::from_secs(60));
+        assert_eq!(meta.etag.as_deref(), Some("\"v1\""));
+    }
+
+    #[test]
+    fn modified_pages_replace_the_cached_one() {
+        let server = StubServer::serve(vec![
+            Response::new(200, "new body").with_header("ETag", "\"v2\"")
+        ]);
+        let url = format!("{}/items/1.json", server.url);
+        let cache = expired_cache(&url);
+
+        let client = WebScraper::new("session".to_string(), true);
+        let body = client.get_one(url.clone(), Some(cache.clone())).unwrap();
+
+        assert_eq!(server.requests()[0].header("If-None-Match"), Some("\"v1\""));
+        assert_eq!(body, "new body");
+        assert_eq!(cache.get(&url).as_deref(), Some("new body"));
+        let meta = cache.get_meta(&url).unwrap();
+        assert_eq!(meta.etag.as_deref(), Some("\"v2\""));
+        assert_eq!(meta.last_modified, None);
+    }
+
+    #[test]
+    fn fresh_pages_are_not_requested() {
+        let cache = Arc::new(Cache::default().with_ttl(Some(Duration::from_secs(60))));
+        cache.add("http://127.0.0.1:1/".to_string(), "cached body".to_string());
+
+        let client = WebScraper::new("session".to_string(), true);
+        let body = client
+            .get_one("http://127.0.0.1:1/".to_string(), Some(cache.clone()))
+            .unwrap();
+
+        assert_eq!(body, "cached body");
+        assert_eq!(cache.get_stats().cache_hits, 1);
+    }
+}
@@ -1,12 +1,23 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use rust_xlsxwriter::{ColNum, Url, Workbook, Worksheet, XlsxError};
+use rust_xlsxwriter::utility::{column_number_to_name, row_col_to_cell};
+use rust_xlsxwriter::{
+    Chart, ColNum, Color, ConditionalFormatFormula, Format, Image, Table, TableStyle, Url,
+    Workbook, Worksheet, XlsxError,
+};
 
 use crate::debug;
+use crate::models::bilingual::{interleave, render_text};
 use crate::models::item_row::ItemRow;
 
 #[derive(Debug, Clone, Copy)]
 enum Headers {
+    Thumbnail,
     ItemName,
     ItemNameTranslated,
     ItemLink,
@@ -17,14 +28,19 @@ enum Headers {
     SecondaryCategory,
     VRChat,
     Adult,
+    SoldOut,
     Tags,
+    CompatibleAvatars,
     Price,
+    PreviousPrice,
     Currency,
     Hearts,
     ImagesNumber,
     ImagesURLs,
     DownloadNumber,
     DownloadsLinks,
+    License,
+    Changelog,
     Markdown,
     MarkdownTranslated,
 }
@@ -32,26 +48,32 @@ enum Headers {
 impl From<Headers> for ColNum {
     fn from(header: Headers) -> Self {
         match header {
-            Headers::ItemName => 0,
-            Headers::ItemNameTranslated => 1,
-            Headers::ItemLink => 2,
-            Headers::AuthorName => 3,
-            Headers::AuthorNameTranslated => 4,
-            Headers::AuthorLink => 5,
-            Headers::PrimaryCategory => 6,
-            Headers::SecondaryCategory => 7,
-            Headers::VRChat => 8,
-            Headers::Adult => 9,
-            Headers::Tags => 10,
-            Headers::Price => 11,
-            Headers::Currency => 12,
-            Headers::Hearts => 13,
-            Headers::ImagesNumber => 14,
-            Headers::ImagesURLs => 15,
-            Headers::DownloadNumber => 16,
-            Headers::DownloadsLinks => 17,
-            Headers::Markdown => 18,
-            Headers::MarkdownTranslated => 19,
+            Headers::Thumbnail => 0,
+            Headers::ItemName => 1,
+            Headers::ItemNameTranslated => 2,
+            Headers::ItemLink => 3,
+            Headers::AuthorName => 4,
+            Headers::AuthorNameTranslated => 5,
+            Headers::AuthorLink => 6,
+            Headers::PrimaryCategory => 7,
+            Headers::SecondaryCategory => 8,
+            Headers::VRChat => 9,
+            Headers::Adult => 10,
+            Headers::SoldOut => 11,
+            Headers::Tags => 12,
+            Headers::CompatibleAvatars => 13,
+            Headers::Price => 14,
+            Headers::PreviousPrice => 15,
+            Headers::Currency => 16,
+            Headers::Hearts => 17,
+            Headers::ImagesNumber => 18,
+            Headers::ImagesURLs => 19,
+            Headers::DownloadNumber => 20,
+            Headers::DownloadsLinks => 21,
+            Headers::License => 22,
+            Headers::Changelog => 23,
+            Headers::Markdown => 24,
+            Headers::MarkdownTranslated => 25,
         }
     }
 }
@@ -59,6 +81,7 @@ impl From<Headers> for ColNum {
 pub fn write_headers(worksheet: &mut Worksheet) -> Result<(), XlsxError> {
     const ROW: u32 = 0;
 
+    worksheet.write(ROW, Headers::Thumbnail.into(), "Thumbnail")?;
     worksheet.write(ROW, Headers::ItemName.into(), "Item Name")?;
     worksheet.write(
         ROW,
@@ -77,14 +100,19 @@ pub fn write_headers(worksheet: &mut Worksheet) -> Result<(), XlsxError> {
     worksheet.write(ROW, Headers::SecondaryCategory.into(), "Secondary Category")?;
     worksheet.write(ROW, Headers::VRChat.into(), "VRChat")?;
     worksheet.write(ROW, Headers::Adult.into(), "Adult")?;
+    worksheet.write(ROW, Headers::SoldOut.into(), "Sold Out")?;
     worksheet.write(ROW, Headers::Tags.into(), "Tags")?;
+    worksheet.write(ROW, Headers::CompatibleAvatars.into(), "Compatible Avatars")?;
     worksheet.write(ROW, Headers::Price.into(), "Price")?;
+    worksheet.write(ROW, Headers::PreviousPrice.into(), "Previous Price")?;
     worksheet.write(ROW, Headers::Currency.into(), "Currency")?;
     worksheet.write(ROW, Headers::Hearts.into(), "Hearts")?;
     worksheet.write(ROW, Headers::ImagesNumber.into(), "Images Number")?;
     worksheet.write(ROW, Headers::ImagesURLs.into(), "Images URLs")?;
     worksheet.write(ROW, Headers::DownloadNumber.into(), "Download Number")?;
     worksheet.write(ROW, Headers::DownloadsLinks.into(), "Downloads Links")?;
+    worksheet.write(ROW, Headers::License.into(), "License")?;
+    worksheet.write(ROW, Headers::Changelog.into(), "Changelog")?;
     worksheet.write(ROW, Headers::Markdown.into(), "Markdown")?;
     worksheet.write(
         ROW,
@@ -95,8 +123,18 @@ pub fn write_headers(worksheet: &mut Worksheet) -> Result<(), XlsxError> {
     Ok(())
 }
 
-pub fn write_row(item: &ItemRow, worksheet: &mut Worksheet, row: u32) -> Result<(), XlsxError> {
+/// Write a single item to `row`.
+///
+/// With `bilingual` set, the translated markdown column shows every original line
+/// with its translation below, instead of only the translation.
+pub fn write_row(
+    item: &ItemRow,
+    worksheet: &mut Worksheet,
+    row: u32,
+    bilingual: bool,
+) -> Result<(), XlsxError> {
     let ItemRow {
+        id: _,
         item_name,
         item_name_translated,
         item_link,
@@ -107,19 +145,29 @@ pub fn write_row(item: &ItemRow, worksheet: &mut Worksheet, row: u32) -> Result<
         secondary_category,
         vrchat,
         adult,
+        sold_out,
         tags,
+        compatible_avatars,
         price,
+        previous_price,
         currency,
         hearts,
         image_urls,
+        thumbnail_url: _,
         download_links,
+        variations: _,
         markdown,
         markdown_translated,
+        description,
     } = item.to_owned();
 
     let item_name_translated = item_name_translated.unwrap_or_else(|| item_name.clone());
     let author_name_translated = author_name_translated.unwrap_or_else(|| author_name.clone());
-    let markdown_translated = markdown_translated.unwrap_or_else(|| markdown.clone());
+    let markdown_translated = match markdown_translated {
+        Some(translated) if bilingual => render_text(&interleave(&markdown, &translated)),
+        Some(translated) => translated,
+        None => markdown.clone(),
+    };
 
     worksheet.write(row, Headers::ItemName.into(), item_name)?;
     worksheet.write(
@@ -139,8 +187,27 @@ pub fn write_row(item: &ItemRow, worksheet: &mut Worksheet, row: u32) -> Result<
     worksheet.write(row, Headers::SecondaryCategory.into(), secondary_category)?;
     worksheet.write_boolean(row, Headers::VRChat.into(), vrchat)?;
     worksheet.write_boolean(row, Headers::Adult.into(), adult)?;
+    worksheet.write_boolean(row, Headers::SoldOut.into(), sold_out)?;
     worksheet.write(row, Headers::Tags.into(), tags.join(", "))?;
-    worksheet.write(row, Headers::Price.into(), price)?;
+    worksheet.write(
+        row,
+        Headers::CompatibleAvatars.into(),
+        compatible_avatars.join(", "),
+    )?;
+    worksheet.write_number_with_format(
+        row,
+        Headers::Price.into(),
+        price,
+        &currency_format(&currency),
+    )?;
+    if let Some(previous_price) = previous_price {
+        worksheet.write_number_with_format(
+            row,
+            Headers::PreviousPrice.into(),
+            previous_price,
+            &currency_format(&currency),
+        )?;
+    }
     worksheet.write(row, Headers::Currency.into(), currency)?;
     worksheet.write(row, Headers::Hearts.into(), hearts)?;
     worksheet.write(
@@ -159,38 +226,486 @@ pub fn write_row(item: &ItemRow, worksheet: &mut Worksheet, row: u32) -> Result<
         Headers::DownloadsLinks.into(),
         download_links.join("\n"),
     )?;
+    worksheet.write(
+        row,
+        Headers::License.into(),
+        description.license().unwrap_or_default(),
+    )?;
+    worksheet.write(
+        row,
+        Headers::Changelog.into(),
+        description.changelog().unwrap_or_default(),
+    )?;
     worksheet.write(row, Headers::Markdown.into(), markdown)?;
     worksheet.write(row, Headers::MarkdownTranslated.into(), markdown_translated)?;
 
     Ok(())
 }
 
-pub fn write_all(worksheet: &mut Worksheet, items: &[ItemRow]) {
+pub fn write_all(worksheet: &mut Worksheet, items: &[ItemRow], bilingual: bool) {
     items.iter().enumerate().for_each(|(idx, item)| {
-        write_row(item, worksheet, u32::try_from(idx).unwrap() + 1).unwrap();
+        write_row(item, worksheet, u32::try_from(idx).unwrap() + 1, bilingual).unwrap();
     });
 }
 
-pub fn format_cols(worksheet: &mut Worksheet) -> Result<(), XlsxError> {
-    worksheet.autofilter(0, 0, 0, Headers::Markdown.into())?;
+/// Name of the worksheet holding one row per item, every other sheet links back into it.
+pub const ITEMS_SHEET: &str = "Items";
+pub const VARIATIONS_SHEET: &str = "Variations";
+pub const SHOPS_SHEET: &str = "Shops";
+pub const TAGS_SHEET: &str = "Tags";
+pub const SUMMARY_SHEET: &str = "Summary";
+
+/// Width and height of the embedded thumbnails, in pixels.
+const THUMBNAIL_SIZE: u16 = 96;
+
+/// Internal hyperlink to the row of the item at `idx` in the items sheet.
+fn item_url(idx: usize, text: impl Into<String>) -> Url {
+    let cell = row_col_to_cell(u32::try_from(idx).unwrap() + 1, Headers::ItemName.into());
+    Url::new(format!("internal:'{ITEMS_SHEET}'!{cell}")).set_text(text)
+}
+
+/// Embed the downloaded thumbnails into the thumbnail column, one per item row,
+/// and make the rows tall enough to fit them.
+pub fn insert_thumbnails(
+    worksheet: &mut Worksheet,
+    thumbnails: &[Option<PathBuf>],
+) -> Result<(), XlsxError> {
+    worksheet.set_column_width_pixels(Headers::Thumbnail.into(), THUMBNAIL_SIZE + 4)?;
+
+    for (idx, thumbnail) in thumbnails.iter().enumerate() {
+        let Some(path) = thumbnail else {
+            continue;
+        };
+        let row = u32::try_from(idx).unwrap() + 1;
+
+        let mut image = match Image::new(path) {
+            Ok(image) => image,
+            Err(err) => {
+                println!("skipping thumbnail {}: {err}", path.display());
+                continue;
+            }
+        };
+        image.set_scale_to_size(THUMBNAIL_SIZE, THUMBNAIL_SIZE, true);
+
+        worksheet.set_row_height_pixels(row, THUMBNAIL_SIZE + 4)?;
+        worksheet.insert_image_with_offset(row, Headers::Thumbnail.into(), &image, 2, 2)?;
+    }
 
     Ok(())
 }
 
-pub fn save_book(workbook: &mut Workbook, path: &'static str) {
-    match workbook.save(path) {
+/// Write one row per variation of every item.
+pub fn write_variations_sheet(
+    worksheet: &mut Worksheet,
+    items: &[ItemRow],
+) -> Result<(), XlsxError> {
+    worksheet.set_name(VARIATIONS_SHEET)?;
+    worksheet.write_row(
+        0,
+        0,
+        [
+            "Item",
+            "Variation",
+            "Price",
+            "Currency",
+            "Status",
+            "Sold Out",
+            "Downloadables",
+        ],
+    )?;
+
+    let mut row = 1;
+    for (idx, item) in items.iter().enumerate() {
+        for variation in &item.variations {
+            worksheet.write(row, 0, item_url(idx, item.item_name.as_str()))?;
+            worksheet.write(row, 1, variation.name.clone().unwrap_or_default())?;
+            worksheet.write(row, 2, variation.price)?;
+            worksheet.write(row, 3, item.currency.as_str())?;
+            worksheet.write(row, 4, variation.status.as_str())?;
+            worksheet.write_boolean(row, 5, variation.sold_out)?;
+            worksheet.write(row, 6, variation.downloadables.join("\n"))?;
+            row += 1;
+        }
+    }
+
+    worksheet.autofilter(0, 0, row.saturating_sub(1).max(1), 6)?;
+
+    Ok(())
+}
+
+/// Write one row per shop with the number of wishlisted items and their total value.
+pub fn write_shops_sheet(worksheet: &mut Worksheet, items: &[ItemRow]) -> Result<(), XlsxError> {
+    // (shop link, currency) -> (shop name, first item index, item count, total value)
+    let mut shops: BTreeMap<(&str, &str), (&str, usize, u32, f64)> = BTreeMap::new();
+    for (idx, item) in items.iter().enumerate() {
+        let shop = shops
+            .entry((item.author_link.as_str(), item.currency.as_str()))
+            .or_insert((item.author_name.as_str(), idx, 0, 0.0));
+        shop.2 += 1;
+        shop.3 += item.price;
+    }
+    let mut shops = shops.into_iter().collect::<Vec<_>>();
+    shops.sort_by(|(_, a), (_, b)| b.2.cmp(&a.2).then(b.3.total_cmp(&a.3)));
+
+    worksheet.set_name(SHOPS_SHEET)?;
+    worksheet.write_row(
+        0,
+        0,
+        [
+            "Shop",
+            "Shop Link",
+            "Items",
+            "Total Value",
+            "Currency",
+            "First Item",
+        ],
+    )?;
+
+    for (row, ((link, currency), (name, first_idx, count, total))) in shops.iter().enumerate() {
+        let row = u32::try_from(row).unwrap() + 1;
+        worksheet.write(row, 0, *name)?;
+        worksheet.write(row, 1, Url::new(*link))?;
+        worksheet.write(row, 2, *count)?;
+        worksheet.write(row, 3, *total)?;
+        worksheet.write(row, 4, *currency)?;
+        worksheet.write(
+            row,
+            5,
+            item_url(*first_idx, items[*first_idx].item_name.as_str()),
+        )?;
+    }
+
+    worksheet.autofilter(0, 0, u32::try_from(shops.len().max(1)).unwrap(), 5)?;
+
+    Ok(())
+}
+
+/// Write one row per tag with how often it appears across all items.
+pub fn write_tags_sheet(worksheet: &mut Worksheet, items: &[ItemRow]) -> Result<(), XlsxError> {
+    // tag -> (first item index, frequency)
+    let mut tags: BTreeMap<&str, (usize, u32)> = BTreeMap::new();
+    for (idx, item) in items.iter().enumerate() {
+        for tag in &item.tags {
+            tags.entry(tag.as_str()).or_insert((idx, 0)).1 += 1;
+        }
+    }
+    let mut tags = tags.into_iter().collect::<Vec<_>>();
+    tags.sort_by_key(|(_, (_, frequency))| Reverse(*frequency));
+
+    worksheet.set_name(TAGS_SHEET)?;
+    worksheet.write_row(0, 0, ["Tag", "Frequency", "First Item"])?;
+
+    for (row, (tag, (first_idx, frequency))) in tags.iter().enumerate() {
+        let row = u32::try_from(row).unwrap() + 1;
+        worksheet.write(row, 0, *tag)?;
+        worksheet.write(row, 1, *frequency)?;
+        worksheet.write(
+            row,
+            2,
+            item_url(*first_idx, items[*first_idx].item_name.as_str()),
+        )?;
+    }
+
+    worksheet.autofilter(0, 0, u32::try_from(tags.len().max(1)).unwrap(), 2)?;
+
+    Ok(())
+}
+
+/// Number format that shows the price in the item's currency.
+fn currency_format(currency: &str) -> Format {
+    let num_format = match currency {
+        "JPY" => "¥#,##0".to_string(),
+        "USD" => "$#,##0.00".to_string(),
+        "EUR" => "€#,##0.00".to_string(),
+        "GBP" => "£#,##0.00".to_string(),
+        "KRW" => "₩#,##0".to_string(),
+        "CNY" => "CN¥#,##0.00".to_string(),
+        other => format!("#,##0.00 \"{other}\""),
+    };
+
+    Format::new().set_num_format(num_format)
+}
+
+/// Write a titled two column block of `(label, value)` rows starting at `row`,
+/// and insert a chart of it to the right.
+///
+/// Returns the row after the block and its chart.
+fn write_summary_block(
+    worksheet: &mut Worksheet,
+    row: u32,
+    title: &str,
+    rows: &[(String, f64)],
+    mut chart: Chart,
+) -> Result<u32, XlsxError> {
+    // a default sized chart is 15 rows tall
+    const CHART_ROWS: u32 = 16;
+
+    worksheet.write_string_with_format(row, 0, title, &Format::new().set_bold())?;
+
+    let first_row = row + 1;
+    for (offset, (label, value)) in rows.iter().enumerate() {
+        let data_row = first_row + u32::try_from(offset).unwrap();
+        worksheet.write(data_row, 0, label.as_str())?;
+        worksheet.write(data_row, 1, *value)?;
+    }
+    let last_row = first_row + u32::try_from(rows.len().max(1)).unwrap() - 1;
+
+    chart.title().set_name(title);
+    chart.legend().set_hidden();
+    chart
+        .add_series()
+        .set_categories((SUMMARY_SHEET, first_row, 0, last_row, 0))
+        .set_values((SUMMARY_SHEET, first_row, 1, last_row, 1));
+    worksheet.insert_chart(row, 3, &chart)?;
+
+    Ok(row + (last_row - row + 2).max(CHART_ROWS))
+}
+
+/// Count the items per key, sorted by count, keeping at most `limit` keys.
+fn count_by<'a>(
+    items: &'a [ItemRow],
+    key: impl Fn(&'a ItemRow) -> &'a str,
+    limit: usize,
+) -> Vec<(String, f64)> {
+    let mut counts: BTreeMap<&str, u32> = BTreeMap::new();
+    for item in items {
+        *counts.entry(key(item)).or_default() += 1;
+    }
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_by_key(|(_, count)| Reverse(*count));
+
+    counts
+        .into_iter()
+        .take(limit)
+        .map(|(key, count)| (key.to_string(), f64::from(count)))
+        .collect()
+}
+
+/// Write the dashboard sheet with totals and charts over all items.
+pub fn write_summary_sheet(worksheet: &mut Worksheet, items: &[ItemRow]) -> Result<(), XlsxError> {
+    worksheet.set_name(SUMMARY_SHEET)?;
+    worksheet.set_column_width(0, 30)?;
+    worksheet.set_column_width(1, 14)?;
+
+    worksheet.write_string_with_format(0, 0, "Items", &Format::new().set_bold())?;
+    worksheet.write(0, 1, u32::try_from(items.len()).unwrap())?;
+
+    let mut value_by_currency: BTreeMap<&str, f64> = BTreeMap::new();
+    for item in items {
+        *value_by_currency.entry(item.currency.as_str()).or_default() += item.price;
+    }
+    let value_by_currency = value_by_currency
+        .into_iter()
+        .map(|(currency, value)| (currency.to_string(), value))
+        .collect::<Vec<_>>();
+
+    let vrchat = items.iter().filter(|item| item.vrchat).count();
+    let vrchat = vec![
+        ("VRChat".to_string(), vrchat as f64),
+        ("Not VRChat".to_string(), (items.len() - vrchat) as f64),
+    ];
+
+    let adult = items.iter().filter(|item| item.adult).count();
+    let adult = vec![
+        ("Adult".to_string(), adult as f64),
+        ("All ages".to_string(), (items.len() - adult) as f64),
+    ];
+
+    let hearts = [(0, 0), (1, 9), (10, 99), (100, 999), (1000, 9999)]
+        .into_iter()
+        .map(|(min, max)| {
+            let count = items
+                .iter()
+                .filter(|item| (min..=max).contains(&item.hearts))
+                .count();
+            (format!("{min}-{max}"), count as f64)
+        })
+        .chain(std::iter::once((
+            "10000+".to_string(),
+            items.iter().filter(|item| item.hearts >= 10000).count() as f64,
+        )))
+        .collect::<Vec<_>>();
+
+    let mut row = 2;
+    row = write_summary_block(
+        worksheet,
+        row,
+        "Total value by currency",
+        &value_by_currency,
+        Chart::new_column(),
+    )?;
+    row = write_summary_block(
+        worksheet,
+        row,
+        "Items per primary category",
+        &count_by(items, |item| item.primary_category.as_str(), usize::MAX),
+        Chart::new_bar(),
+    )?;
+    row = write_summary_block(
+        worksheet,
+        row,
+        "Items per secondary category",
+        &count_by(items, |item| item.secondary_category.as_str(), usize::MAX),
+        Chart::new_bar(),
+    )?;
+    row = write_summary_block(
+        worksheet,
+        row,
+        "Top shops",
+        &count_by(items, |item| item.author_name.as_str(), 20),
+        Chart::new_bar(),
+    )?;
+    row = write_summary_block(worksheet, row, "VRChat", &vrchat, Chart::new_pie())?;
+    row = write_summary_block(worksheet, row, "Adult share", &adult, Chart::new_pie())?;
+    write_summary_block(worksheet, row, "Hearts", &hearts, Chart::new_column())?;
+
+    Ok(())
+}
+
+/// Turn the items sheet into an Excel table and style it.
+///
+/// The header row and the item name column are frozen, long text columns wrap,
+/// and adult, VRChat, sold out and price changed rows are highlighted.
+pub fn format_cols(worksheet: &mut Worksheet, items: &[ItemRow]) -> Result<(), XlsxError> {
+    const FIRST_COL: Headers = Headers::Thumbnail;
+    const LAST_COL: Headers = Headers::MarkdownTranslated;
+
+    let last_row = u32::try_from(items.len().max(1)).unwrap();
+
+    let mut table = Table::new();
+    table.set_name("Items").set_style(TableStyle::Medium2);
+    worksheet.add_table(0, FIRST_COL.into(), last_row, LAST_COL.into(), &table)?;
+
+    worksheet.set_freeze_panes(1, ColNum::from(Headers::ItemName) + 1)?;
+
+    for (header, width) in [
+        (Headers::ItemName, 40),
+        (Headers::ItemNameTranslated, 40),
+        (Headers::ItemLink, 20),
+        (Headers::AuthorName, 20),
+        (Headers::AuthorNameTranslated, 20),
+        (Headers::AuthorLink, 20),
+        (Headers::PrimaryCategory, 16),
+        (Headers::SecondaryCategory, 16),
+        (Headers::Tags, 30),
+        (Headers::CompatibleAvatars, 20),
+        (Headers::Price, 12),
+        (Headers::PreviousPrice, 12),
+        (Headers::ImagesURLs, 20),
+        (Headers::DownloadsLinks, 20),
+        (Headers::License, 40),
+        (Headers::Changelog, 40),
+        (Headers::Markdown, 60),
+        (Headers::MarkdownTranslated, 60),
+    ] {
+        worksheet.set_column_width(header.into(), width)?;
+    }
+
+    let wrap = Format::new().set_text_wrap();
+    worksheet.set_column_format(Headers::ItemName.into(), &wrap)?;
+    worksheet.set_column_format(Headers::ItemNameTranslated.into(), &wrap)?;
+    worksheet.set_column_format(Headers::License.into(), &wrap)?;
+    worksheet.set_column_format(Headers::Changelog.into(), &wrap)?;
+    worksheet.set_column_format(Headers::Markdown.into(), &wrap)?;
+    worksheet.set_column_format(Headers::MarkdownTranslated.into(), &wrap)?;
+
+    // earlier rules win when several match
+    for (header, rule, color) in [
+        (Headers::SoldOut, "=TRUE", 0x00D9_D9D9),
+        (Headers::PreviousPrice, "<>\"\"", 0x00FF_EB9C),
+        (Headers::Adult, "=TRUE", 0x00FF_C7CE),
+        (Headers::VRChat, "=TRUE", 0x00DD_EBF7),
+    ] {
+        let col = column_number_to_name(header.into());
+        let conditional_format = ConditionalFormatFormula::new()
+            .set_rule(format!("=${col}2{rule}").as_str())
+            .set_stop_if_true(true)
+            .set_format(Format::new().set_background_color(Color::RGB(color)));
+        worksheet.add_conditional_format(
+            1,
+            FIRST_COL.into(),
+            last_row,
+            LAST_COL.into(),
+            &conditional_format,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// `path` with the current unix time appended to the file name, e.g. `book-1696000000.xlsx`.
+fn timestamped_path(path: &Path) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let stem = path
+        .file_stem()
+        .map_or_else(|| "book".into(), |stem| stem.to_string_lossy());
+    let extension = path
+        .extension()
+        .map_or_else(|| "xlsx".into(), |extension| extension.to_string_lossy());
+
+    path.with_file_name(format!("{stem}-{timestamp}.{extension}"))
+}
+
+/// Save the workbook to `path`.
+///
+/// The workbook is written to a temporary file next to `path` and then renamed over it,
+/// so a failed save never leaves a half written workbook behind.
+/// If `path` can't be replaced, e.g. because it is open in Excel,
+/// the workbook is saved under a timestamped name instead.
+///
+/// Returns the path the workbook was actually saved to.
+pub fn save_book(workbook: &mut Workbook, path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent).unwrap_or_else(|e| {
+            panic!(
+                "failed to create `{}` because of error: {e}",
+                parent.display()
+            )
+        });
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    if let Err(e) = workbook.save(&tmp_path) {
+        panic!(
+            "failed to save `{}` because of error: {e}",
+            tmp_path.display()
+        );
+    }
+
+    match fs::rename(&tmp_path, path) {
         Ok(()) => {
             debug!("saved");
+            path.to_path_buf()
         }
-        Err(e) => match e {
-            #[allow(unused_variables)]
-            XlsxError::IoError(e) => panic!(
-                "io error: {e}\n\
-                Did you check if the file is already open in excel?"
-            ),
-            _ => {
-                panic!("error: {e}");
-            }
-        },
-    };
+        Err(e) => {
+            let fallback = timestamped_path(path);
+            println!(
+                "failed to replace `{}` because of error: {e}\n\
+                Did you check if the file is already open in excel?\n\
+                saving to `{}` instead",
+                path.display(),
+                fallback.display()
+            );
+
+            fs::rename(&tmp_path, &fallback).unwrap_or_else(|e| {
+                panic!(
+                    "failed to save `{}` because of error: {e}, the workbook is left at `{}`",
+                    fallback.display(),
+                    tmp_path.display()
+                )
+            });
+
+            fallback
+        }
+    }
 }
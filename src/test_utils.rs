@@ -0,0 +1,153 @@
+//! Fixtures shared by the tests.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+use std::{env, fs, process};
+
+/// A fresh directory in the system temp dir, removed again when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` only has to tell the tests apart when a directory is left behind.
+    pub fn new(name: &str) -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let path = env::temp_dir().join(format!(
+            "booth-archiver-{name}-{}-{}",
+            process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        Self(path)
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// What the [`StubServer`] answers to one request.
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: String,
+}
+
+impl Response {
+    pub fn json(body: &str) -> Self {
+        Self::new(200, body).with_header("Content-Type", "application/json")
+    }
+
+    pub fn new(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: body.to_string(),
+        }
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+}
+
+/// A request the [`StubServer`] got.
+pub struct Request {
+    /// the request line and the headers
+    pub head: String,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The value of the header `name`, compared case insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.head.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+}
+
+/// A local http server that answers one request with each of its responses, in order.
+pub struct StubServer {
+    pub url: String,
+    server: JoinHandle<Vec<Request>>,
+}
+
+impl StubServer {
+    pub fn serve(responses: Vec<Response>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            responses
+                .into_iter()
+                .map(|response| {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream);
+                    let request = read_request(&mut reader);
+                    write_response(reader.get_mut(), &response);
+                    request
+                })
+                .collect()
+        });
+
+        Self { url, server }
+    }
+
+    /// Wait until every response was sent, and hand back the requests.
+    pub fn requests(self) -> Vec<Request> {
+        self.server.join().unwrap()
+    }
+}
+
+fn read_request(reader: &mut impl BufRead) -> Request {
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line == "\r\n" || line.is_empty() {
+            break;
+        }
+        head.push_str(&line);
+    }
+
+    let mut request = Request { head, body: vec![] };
+    let len = request
+        .header("Content-Length")
+        .map_or(0, |len| len.parse().unwrap());
+    request.body = vec![0; len];
+    reader.read_exact(&mut request.body).unwrap();
+
+    request
+}
+
+fn write_response(stream: &mut impl Write, response: &Response) {
+    let mut head = format!("HTTP/1.1 {} Stub\r\n", response.status);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body.len()
+    ));
+
+    stream.write_all(head.as_bytes()).unwrap();
+    stream.write_all(response.body.as_bytes()).unwrap();
+}
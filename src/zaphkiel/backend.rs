@@ -0,0 +1,1403 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use path_absolutize::Absolutize;
+use redb::{Database, Durability, ReadableTable, ReadableTableMetadata, TableDefinition};
+use ron::ser::PrettyConfig;
+use serde::de::DeserializeOwned;
+use serde::ser::Error as _;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::time_it;
+use crate::zaphkiel::cache::EntryMeta;
+
+/// Where the key-value pairs of a [`Cache`](crate::zaphkiel::cache::Cache) live.
+///
+/// Backends are shared between threads, so every method takes `&self`.
+#[allow(clippy::module_name_repetitions)]
+pub trait CacheBackend: Debug + Send + Sync {
+    fn get(&self, key: &str) -> Option<String>;
+
+    fn insert(&self, key: String, value: String);
+
+    fn remove(&self, key: &str) -> Option<String>;
+
+    fn keys(&self) -> Vec<String>;
+
+    fn entries(&self) -> Vec<(String, String)>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn clear(&self);
+
+    /// when and how the value of `key` was fetched
+    fn meta(&self, key: &str) -> Option<EntryMeta>;
+
+    fn set_meta(&self, key: String, meta: EntryMeta);
+
+    /// insert a value along with its metadata, both or neither are written
+    fn insert_with_meta(&self, key: String, value: String, meta: EntryMeta) {
+        self.set_meta(key.clone(), meta);
+        self.insert(key, value);
+    }
+
+    /// re-read the entries from disk, dropping unsaved changes
+    fn reload(&self);
+
+    /// make every change so far persistent
+    fn flush(&self);
+
+    /// drop the metadata of entries that are gone and give unused space back,
+    /// needs the backend to itself
+    fn vacuum(&mut self);
+
+    /// how many values were dropped from memory to stay within a memory budget
+    fn evictions(&self) -> u64 {
+        0
+    }
+}
+
+/// The storage format of the cache files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CacheFormat {
+    /// one pretty ron file, read in full on startup and rewritten in full on every dump
+    #[default]
+    Ron,
+    /// an embedded redb database, entries are read and written one at a time
+    Redb,
+    /// like `Ron`, but compressed with zstd
+    RonZstd,
+}
+
+impl CacheFormat {
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Ron => "ron",
+            Self::Redb => "redb",
+            Self::RonZstd => "ron.zst",
+        }
+    }
+
+    /// The format of a cache file, going by its extension.
+    #[must_use]
+    pub fn from_path(path: &Path) -> Self {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        if name.ends_with(".redb") {
+            Self::Redb
+        } else if name.ends_with(".ron.zst") {
+            Self::RonZstd
+        } else {
+            Self::Ron
+        }
+    }
+
+    /// Open the cache file at `path` in this format.
+    #[must_use]
+    pub fn open(self, path: PathBuf) -> Box<dyn CacheBackend> {
+        match self {
+            Self::Ron | Self::RonZstd => Box::new(RonFile::open(path)),
+            Self::Redb => Box::new(RedbFile::open(path)),
+        }
+    }
+
+    /// Open the cache file at `path` in this format, keeping at most `budget` bytes in memory.
+    ///
+    /// Ron caches move their values to a redb file next to them while they are open,
+    /// half the budget keeps the most recently used values in memory and half is redb's page cache,
+    /// see [`SpilledRon`] and [`Lru`]. Redb caches only read the values that are used anyway,
+    /// so the budget bounds their page cache.
+    #[must_use]
+    pub fn open_with_budget(self, path: PathBuf, budget: Option<usize>) -> Box<dyn CacheBackend> {
+        let Some(budget) = budget else {
+            return self.open(path);
+        };
+
+        match self {
+            Self::Ron | Self::RonZstd => {
+                let page_cache = budget / 2;
+                let spilled = SpilledRon::open(path, page_cache);
+                Box::new(Lru::new(Box::new(spilled), budget - page_cache))
+            }
+            Self::Redb => Box::new(RedbFile::open_with_cache_size(path, Some(budget))),
+        }
+    }
+}
+
+/// Whether the file at `path` is zstd compressed, going by its extension.
+fn is_compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "zst")
+}
+
+/// Read a text file, decompressing it if `compressed`.
+fn read_text(path: &Path, compressed: bool) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+    let bytes = if compressed {
+        zstd::decode_all(bytes.as_slice())?
+    } else {
+        bytes
+    };
+
+    String::from_utf8(bytes).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Read a ron cache file, compressed if its extension is `.zst`.
+pub fn read_ron(path: &Path) -> HashMap<String, String> {
+    let abs_path = path.absolutize().expect("failed to absolutize path");
+    let abs_path = abs_path.to_str().expect("failed to convert path to str");
+
+    let cache = time_it!("reading from cache file" =>
+    read_text(path, is_compressed(path))
+        .unwrap_or_else(|e| panic!("failed to read {} because of error: {}", abs_path, e))
+    );
+
+    time_it!("converting to hashmap from string" =>
+        ron::from_str(&cache)
+            .unwrap_or_else(|e| panic!("Failed to parse {}, \
+            {} exists but the ron data is invalid\n\
+            failed with error: {}", abs_path, abs_path, e))
+    )
+}
+
+/// `path` with `suffix` appended, e.g. `cache/all_items.ron.bak`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(suffix);
+
+    PathBuf::from(path)
+}
+
+/// The path of the backup `write_atomic` keeps of `path`.
+#[must_use]
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, "bak")
+}
+
+/// Write `contents` to `path` so that a crash never leaves a half written file behind.
+///
+/// The contents are written to `<path>.tmp` and synced to disk first, then the current file
+/// becomes the backup `<path>.bak` and the temporary file is renamed to `path`.
+pub fn write_atomic(path: &Path, contents: &[u8]) {
+    write_atomic_with(path, |file| file.write_all(contents));
+}
+
+/// Like [`write_atomic`], with `write` writing the contents to the temporary file.
+pub fn write_atomic_with(path: &Path, write: impl FnOnce(&mut File) -> io::Result<()>) {
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty());
+    if let Some(parent) = parent {
+        fs::create_dir_all(parent).unwrap_or_else(|e| {
+            panic!(
+                "failed to create `{}` because of error: {e}",
+                parent.display()
+            )
+        });
+    }
+
+    let tmp_path = with_suffix(path, "tmp");
+    let mut file = File::create(&tmp_path).unwrap_or_else(|e| {
+        panic!(
+            "Failed to create file `{}` due to error: {e}`",
+            tmp_path.display()
+        )
+    });
+    write(&mut file)
+        .and_then(|()| file.sync_all())
+        .unwrap_or_else(|e| panic!("failed to write to cache file because of error: {}", e));
+
+    if fs::metadata(path).is_ok() {
+        fs::rename(path, backup_path(path)).unwrap_or_else(|e| {
+            panic!(
+                "failed to back up `{}` because of error: {e}",
+                path.display()
+            )
+        });
+    }
+
+    fs::rename(&tmp_path, path).unwrap_or_else(|e| {
+        panic!(
+            "failed to replace `{}` because of error: {e}",
+            path.display()
+        )
+    });
+
+    // make the renames themselves durable, not every platform can open a directory
+    if let Ok(dir) = File::open(parent.unwrap_or_else(|| Path::new("."))) {
+        let _ = dir.sync_all();
+    }
+}
+
+/// Write a ron cache file, compressed if its extension is `.zst`, see [`write_atomic`].
+///
+/// The ron is streamed to the file, so only as much of `cache` is in memory as it keeps itself.
+pub fn write_ron<T: Serialize>(path: &Path, cache: &T) {
+    write_atomic_with(path, |file| {
+        let ron_err = |e: ron::Error| io::Error::new(ErrorKind::InvalidData, e);
+
+        if is_compressed(path) {
+            let mut encoder = zstd::Encoder::new(BufWriter::new(file), 0)?;
+            ron::ser::to_writer_pretty(&mut encoder, cache, PrettyConfig::default())
+                .map_err(ron_err)?;
+            encoder.finish()?.flush()
+        } else {
+            let mut writer = BufWriter::new(file);
+            ron::ser::to_writer_pretty(&mut writer, cache, PrettyConfig::default())
+                .map_err(ron_err)?;
+            writer.flush()
+        }
+    });
+}
+
+/// Read a ron file written by [`write_ron`].
+///
+/// If the file is missing or corrupt, e.g. because the program was killed while writing it
+/// before writes were atomic, the backup is used instead, and if that is unusable as well
+/// the cache starts out empty. Both print a warning.
+pub fn load_ron<T: DeserializeOwned + Default>(path: &Path) -> T {
+    let backup = backup_path(path);
+
+    for candidate in [path, backup.as_path()] {
+        let text = match read_text(candidate, is_compressed(path)) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => {
+                println!(
+                    "warning: failed to read `{}` because of error: {e}",
+                    candidate.display()
+                );
+                continue;
+            }
+        };
+
+        match time_it!("converting to hashmap from string" => ron::from_str(&text)) {
+            Ok(cache) => {
+                if candidate != path {
+                    println!(
+                        "warning: `{}` is missing or corrupt, using the backup `{}`",
+                        path.display(),
+                        candidate.display()
+                    );
+                }
+                return cache;
+            }
+            Err(e) => println!(
+                "warning: `{}` is corrupt, the ron data is invalid\n\
+                failed with error: {e}",
+                candidate.display()
+            ),
+        }
+    }
+
+    if fs::metadata(path).is_ok() || fs::metadata(&backup).is_ok() {
+        println!(
+            "warning: no usable copy of `{}`, starting with an empty cache",
+            path.display()
+        );
+    }
+
+    T::default()
+}
+
+/// The whole cache in memory, saved as one ron file.
+///
+/// The metadata of the entries is saved next to it, in `<name>.meta.ron`.
+///
+/// The entries are kept in sharded maps, so threads only contend when they touch the same shard.
+#[derive(Debug, Default)]
+pub struct RonFile {
+    cache: DashMap<String, String>,
+    meta: DashMap<String, EntryMeta>,
+    path: PathBuf,
+}
+
+impl RonFile {
+    #[must_use]
+    pub fn open(path: PathBuf) -> Self {
+        let cache = time_it!("reading from cache file" => load_ron(&path));
+        let meta = load_ron(&meta_path(&path));
+
+        Self { cache, meta, path }
+    }
+}
+
+/// The file name of a cache without its extension, e.g. `all_items` for `cache/all_items.ron.zst`.
+fn cache_stem(path: &Path) -> String {
+    let extension = CacheFormat::from_path(path).extension();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+    name.strip_suffix(&format!(".{extension}"))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+/// The path of the metadata file of a ron cache.
+#[must_use]
+pub fn meta_path(path: &Path) -> PathBuf {
+    let extension = CacheFormat::from_path(path).extension();
+
+    path.with_file_name(format!("{}.meta.{extension}", cache_stem(path)))
+}
+
+/// Where [`SpilledRon`] keeps the values of the ron cache at `path` while it is open.
+#[must_use]
+pub fn spill_path(path: &Path) -> PathBuf {
+    path.with_file_name(format!("{}.spill.redb", cache_stem(path)))
+}
+
+/// Whether `path` holds the entries of a cache, rather than their metadata or another file.
+#[must_use]
+pub fn is_cache_file(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let format = CacheFormat::from_path(path);
+    if !name.ends_with(&format!(".{}", format.extension()))
+        || name.contains(".meta.")
+        || name.contains(".spill.")
+    {
+        return false;
+    }
+
+    // plain ron files like `last_page.ron` live next to the caches,
+    // so only files that start like a cache of their format count
+    let len = 64;
+    let header = File::open(path).and_then(|file| match format {
+        CacheFormat::Ron | CacheFormat::Redb => read_prefix(file, len),
+        CacheFormat::RonZstd => read_prefix(zstd::Decoder::new(file)?, len),
+    });
+    let Ok(header) = header else {
+        return false;
+    };
+
+    match format {
+        CacheFormat::Redb => header.starts_with(&REDB_MAGIC),
+        CacheFormat::Ron | CacheFormat::RonZstd => header
+            .iter()
+            .find(|byte| !byte.is_ascii_whitespace())
+            .is_some_and(|&byte| byte == b'{'),
+    }
+}
+
+/// The first bytes of every redb database.
+const REDB_MAGIC: [u8; 9] = [b'r', b'e', b'd', b'b', 0x1A, 0x0A, 0xA9, 0x0D, 0x0A];
+
+/// The first `len` bytes of `reader`, or all of them if there are fewer.
+fn read_prefix(reader: impl Read, len: u64) -> io::Result<Vec<u8>> {
+    let mut prefix = vec![];
+    reader.take(len).read_to_end(&mut prefix)?;
+    Ok(prefix)
+}
+
+/// Copy the cache at `from` into a new cache in the `to` format next to it,
+/// then remove the old files.
+///
+/// Returns the new path and the size of the old and the new files in bytes,
+/// or `None` if `from` is not a cache file.
+pub fn migrate(from: &Path, to: CacheFormat) -> Option<(PathBuf, u64, u64)> {
+    let format = CacheFormat::from_path(from);
+    if format == to {
+        return None;
+    }
+
+    if !is_cache_file(from) {
+        return None;
+    }
+
+    let name = from.file_name()?.to_string_lossy();
+    let stem = name.strip_suffix(&format!(".{}", format.extension()))?;
+    let to_path = from.with_file_name(format!("{stem}.{}", to.extension()));
+
+    let old_files = [from.to_path_buf(), meta_path(from)];
+    let old_size = files_size(&old_files);
+
+    {
+        let source = format.open(from.to_path_buf());
+        let dest = to.open(to_path.clone());
+        for (key, value) in source.entries() {
+            match source.meta(&key) {
+                Some(meta) => dest.insert_with_meta(key, value, meta),
+                None => dest.insert(key, value),
+            }
+        }
+        dest.flush();
+    }
+
+    for path in old_files
+        .iter()
+        .flat_map(|path| [backup_path(path), path.clone()])
+    {
+        if fs::metadata(&path).is_ok() {
+            fs::remove_file(&path).unwrap_or_else(|e| {
+                panic!(
+                    "failed to remove `{}` because of error: {e}",
+                    path.display()
+                )
+            });
+        }
+    }
+
+    let new_files = [to_path.clone(), meta_path(&to_path)];
+    let new_size = files_size(&new_files);
+
+    Some((to_path, old_size, new_size))
+}
+
+/// The size of the cache at `path` in bytes, along with its metadata.
+#[must_use]
+pub fn cache_size(path: &Path) -> u64 {
+    files_size(&[path.to_path_buf(), meta_path(path)])
+}
+
+/// The total size of the files that exist.
+fn files_size(paths: &[PathBuf]) -> u64 {
+    paths
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+impl CacheBackend for RonFile {
+    fn get(&self, key: &str) -> Option<String> {
+        self.cache.get(key).map(|value| value.clone())
+    }
+
+    fn insert(&self, key: String, value: String) {
+        self.cache.insert(key, value);
+    }
+
+    fn remove(&self, key: &str) -> Option<String> {
+        self.meta.remove(key);
+        self.cache.remove(key).map(|(_, value)| value)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.cache.iter().map(|entry| entry.key().clone()).collect()
+    }
+
+    fn entries(&self) -> Vec<(String, String)> {
+        self.cache
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    fn clear(&self) {
+        self.cache.clear();
+        self.meta.clear();
+    }
+
+    fn meta(&self, key: &str) -> Option<EntryMeta> {
+        self.meta.get(key).map(|meta| meta.clone())
+    }
+
+    fn set_meta(&self, key: String, meta: EntryMeta) {
+        self.meta.insert(key, meta);
+    }
+
+    fn reload(&self) {
+        let cache: HashMap<String, String> = load_ron(&self.path);
+        let meta: HashMap<String, EntryMeta> = load_ron(&meta_path(&self.path));
+
+        self.cache.clear();
+        cache.into_iter().for_each(|(key, value)| {
+            self.cache.insert(key, value);
+        });
+        self.meta.clear();
+        meta.into_iter().for_each(|(key, meta)| {
+            self.meta.insert(key, meta);
+        });
+    }
+
+    fn flush(&self) {
+        write_ron(&self.path, &self.cache);
+
+        let meta_path = meta_path(&self.path);
+        if !self.meta.is_empty() || fs::metadata(&meta_path).is_ok() {
+            write_ron(&meta_path, &self.meta);
+        }
+    }
+
+    fn vacuum(&mut self) {
+        self.meta.retain(|key, _| self.cache.contains_key(key));
+        self.flush();
+    }
+}
+
+type Table = TableDefinition<'static, &'static str, &'static str>;
+
+const TABLE: Table = TableDefinition::new("cache");
+/// the metadata of the entries, as json
+const META: Table = TableDefinition::new("meta");
+
+/// The cache in a redb database, only the entries that are used are read.
+///
+/// Writes are committed without syncing, `flush` makes them durable.
+pub struct RedbFile {
+    db: Database,
+    path: PathBuf,
+}
+
+impl Debug for RedbFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedbFile")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl RedbFile {
+    /// Open the database at `path`, creating it if needed.
+    ///
+    /// A new database starts with the entries and metadata of the ron cache of the same name,
+    /// compressed or not, if there is one.
+    /// A corrupt ron cache is recovered like [`load_ron`] does.
+    #[must_use]
+    pub fn open(path: PathBuf) -> Self {
+        Self::open_with_cache_size(path, None)
+    }
+
+    /// Like [`open`](Self::open), with redb keeping at most `cache_size` bytes of pages in memory.
+    #[must_use]
+    pub fn open_with_cache_size(path: PathBuf, cache_size: Option<usize>) -> Self {
+        let is_new = fs::metadata(&path).is_err();
+        let backend = Self::create(path, cache_size);
+
+        let legacy = [CacheFormat::Ron, CacheFormat::RonZstd]
+            .map(|format| backend.path.with_extension(format.extension()))
+            .into_iter()
+            .find(|path| fs::metadata(path).is_ok());
+        if let Some(ron_path) = legacy.filter(|_| is_new) {
+            time_it!("importing the ron cache" => backend.import(&ron_path));
+        }
+        backend.flush();
+
+        backend
+    }
+
+    /// Open the database at `path`, creating it and its tables if needed.
+    fn create(path: PathBuf, cache_size: Option<usize>) -> Self {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap_or_else(|e| {
+                panic!(
+                    "failed to create {} because of error: {}",
+                    parent.display(),
+                    e
+                )
+            });
+        }
+
+        let mut builder = Database::builder();
+        if let Some(cache_size) = cache_size {
+            builder.set_cache_size(cache_size);
+        }
+        let db = builder.create(&path).unwrap_or_else(|e| {
+            panic!("failed to open {} because of error: {}", path.display(), e)
+        });
+
+        let backend = Self { db, path };
+        // create the tables so reads never miss them
+        backend.write(|_, _| ());
+
+        backend
+    }
+
+    /// Add the entries and metadata of the ron cache at `ron_path`,
+    /// a corrupt one is recovered like [`load_ron`] does.
+    fn import(&self, ron_path: &Path) {
+        let cache: HashMap<String, String> = load_ron(ron_path);
+        let meta: HashMap<String, EntryMeta> = load_ron(&meta_path(ron_path));
+
+        self.write(|table, meta_table| {
+            for (key, value) in &cache {
+                table
+                    .insert(key.as_str(), value.as_str())
+                    .unwrap_or_else(|e| panic!("failed to write `{key}` because of error: {e}"));
+                if let Some(meta) = meta.get(key) {
+                    let meta_json = serde_json::to_string(meta).unwrap();
+                    meta_table
+                        .insert(key.as_str(), meta_json.as_str())
+                        .unwrap_or_else(|e| {
+                            panic!("failed to write `{key}` because of error: {e}")
+                        });
+                }
+            }
+        });
+    }
+
+    fn read<T>(
+        &self,
+        definition: Table,
+        f: impl FnOnce(&redb::ReadOnlyTable<&str, &str>) -> T,
+    ) -> T {
+        let txn = self.db.begin_read().unwrap_or_else(|e| {
+            panic!(
+                "failed to read {} because of error: {}",
+                self.path.display(),
+                e
+            )
+        });
+        let table = txn.open_table(definition).unwrap_or_else(|e| {
+            panic!(
+                "failed to read {} because of error: {}",
+                self.path.display(),
+                e
+            )
+        });
+
+        f(&table)
+    }
+
+    /// Change the entries and their metadata in one transaction.
+    fn write<T>(
+        &self,
+        f: impl FnOnce(&mut redb::Table<&str, &str>, &mut redb::Table<&str, &str>) -> T,
+    ) -> T {
+        let mut txn = self.db.begin_write().unwrap_or_else(|e| {
+            panic!(
+                "failed to write to {} because of error: {}",
+                self.path.display(),
+                e
+            )
+        });
+        txn.set_durability(Durability::None);
+
+        let ret = {
+            let open = |definition| {
+                txn.open_table(definition).unwrap_or_else(|e| {
+                    panic!(
+                        "failed to write to {} because of error: {}",
+                        self.path.display(),
+                        e
+                    )
+                })
+            };
+            f(&mut open(TABLE), &mut open(META))
+        };
+
+        txn.commit().unwrap_or_else(|e| {
+            panic!(
+                "failed to write to {} because of error: {}",
+                self.path.display(),
+                e
+            )
+        });
+
+        ret
+    }
+}
+
+impl CacheBackend for RedbFile {
+    fn get(&self, key: &str) -> Option<String> {
+        self.read(TABLE, |table| {
+            table
+                .get(key)
+                .unwrap_or_else(|e| panic!("failed to read `{key}` because of error: {e}"))
+                .map(|value| value.value().to_string())
+        })
+    }
+
+    fn insert(&self, key: String, value: String) {
+        self.write(|table, _| {
+            table
+                .insert(key.as_str(), value.as_str())
+                .unwrap_or_else(|e| panic!("failed to write `{key}` because of error: {e}"));
+        });
+    }
+
+    fn remove(&self, key: &str) -> Option<String> {
+        self.write(|table, meta| {
+            meta.remove(key)
+                .unwrap_or_else(|e| panic!("failed to remove `{key}` because of error: {e}"));
+            table
+                .remove(key)
+                .unwrap_or_else(|e| panic!("failed to remove `{key}` because of error: {e}"))
+                .map(|value| value.value().to_string())
+        })
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.read(TABLE, |table| {
+            table
+                .iter()
+                .expect("failed to iterate over the cache")
+                .map(|entry| {
+                    let (key, _) = entry.expect("failed to read a cache entry");
+                    key.value().to_string()
+                })
+                .collect()
+        })
+    }
+
+    fn entries(&self) -> Vec<(String, String)> {
+        self.read(TABLE, |table| {
+            table
+                .iter()
+                .expect("failed to iterate over the cache")
+                .map(|entry| {
+                    let (key, value) = entry.expect("failed to read a cache entry");
+                    (key.value().to_string(), value.value().to_string())
+                })
+                .collect()
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.read(TABLE, |table| {
+            usize::try_from(table.len().expect("failed to count the cache entries")).unwrap()
+        })
+    }
+
+    fn clear(&self) {
+        self.write(|table, meta| {
+            table
+                .retain(|_, _| false)
+                .expect("failed to clear the cache");
+            meta.retain(|_, _| false)
+                .expect("failed to clear the cache");
+        });
+    }
+
+    fn meta(&self, key: &str) -> Option<EntryMeta> {
+        self.read(META, |meta| {
+            meta.get(key)
+                .unwrap_or_else(|e| panic!("failed to read `{key}` because of error: {e}"))
+                .map(|meta| {
+                    serde_json::from_str(meta.value()).unwrap_or_else(|e| {
+                        panic!("failed to parse the metadata of `{key}` because of error: {e}")
+                    })
+                })
+        })
+    }
+
+    fn set_meta(&self, key: String, meta: EntryMeta) {
+        let meta_json = serde_json::to_string(&meta).unwrap();
+
+        self.write(|_, meta| {
+            meta.insert(key.as_str(), meta_json.as_str())
+                .unwrap_or_else(|e| panic!("failed to write `{key}` because of error: {e}"));
+        });
+    }
+
+    fn insert_with_meta(&self, key: String, value: String, meta: EntryMeta) {
+        let meta_json = serde_json::to_string(&meta).unwrap();
+
+        self.write(|table, meta| {
+            table
+                .insert(key.as_str(), value.as_str())
+                .unwrap_or_else(|e| panic!("failed to write `{key}` because of error: {e}"));
+            meta.insert(key.as_str(), meta_json.as_str())
+                .unwrap_or_else(|e| panic!("failed to write `{key}` because of error: {e}"));
+        });
+    }
+
+    fn reload(&self) {}
+
+    fn vacuum(&mut self) {
+        self.write(|table, meta| {
+            meta.retain(|key, _| {
+                table
+                    .get(key)
+                    .unwrap_or_else(|e| panic!("failed to read `{key}` because of error: {e}"))
+                    .is_some()
+            })
+            .expect("failed to vacuum the metadata");
+        });
+        self.flush();
+
+        self.db.compact().unwrap_or_else(|e| {
+            panic!(
+                "failed to compact {} because of error: {}",
+                self.path.display(),
+                e
+            )
+        });
+    }
+
+    fn flush(&self) {
+        let txn = self.db.begin_write().unwrap_or_else(|e| {
+            panic!(
+                "failed to write to {} because of error: {}",
+                self.path.display(),
+                e
+            )
+        });
+        txn.commit().unwrap_or_else(|e| {
+            panic!(
+                "failed to write to {} because of error: {}",
+                self.path.display(),
+                e
+            )
+        });
+    }
+}
+
+/// Serializes a redb table as a map, one entry at a time, with its values converted by `F`.
+struct TableMap<'a, F>(&'a redb::ReadOnlyTable<&'static str, &'static str>, F);
+
+impl<F: Fn(&str) -> V, V: Serialize> Serialize for TableMap<'_, F> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entries = self.0.iter().map_err(S::Error::custom)?;
+
+        serializer.collect_map(entries.map(|entry| {
+            let (key, value) = entry.expect("failed to read a cache entry");
+            (key.value().to_string(), (self.1)(value.value()))
+        }))
+    }
+}
+
+/// Removes a file when dropped.
+#[derive(Debug)]
+struct RemoveOnDrop(PathBuf);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// A ron cache whose values are moved to a redb file next to it while it is open,
+/// e.g. `cache/all_items.spill.redb`, so they don't all have to be in memory.
+/// Wrapped in an [`Lru`], the most recently used ones still are.
+///
+/// The ron file stays the cache, flushes stream the entries from redb into it one at a time,
+/// and the redb file is removed again when the cache is closed.
+#[derive(Debug)]
+pub struct SpilledRon {
+    spill: RedbFile,
+    path: PathBuf,
+    /// declared after `spill`, so the database is closed before its file is removed
+    _spill_file: RemoveOnDrop,
+}
+
+impl SpilledRon {
+    /// Open the ron cache at `path`, with redb keeping at most `cache_size` bytes of pages in memory.
+    #[must_use]
+    pub fn open(path: PathBuf, cache_size: usize) -> Self {
+        let spill_path = spill_path(&path);
+        // left behind by a run that was killed, the ron file has everything that was flushed
+        let _ = fs::remove_file(&spill_path);
+
+        let spill = RedbFile::create(spill_path.clone(), Some(cache_size));
+        time_it!("reading from cache file" => spill.import(&path));
+
+        Self {
+            spill,
+            path,
+            _spill_file: RemoveOnDrop(spill_path),
+        }
+    }
+}
+
+impl CacheBackend for SpilledRon {
+    fn get(&self, key: &str) -> Option<String> {
+        self.spill.get(key)
+    }
+
+    fn insert(&self, key: String, value: String) {
+        self.spill.insert(key, value);
+    }
+
+    fn remove(&self, key: &str) -> Option<String> {
+        self.spill.remove(key)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.spill.keys()
+    }
+
+    fn entries(&self) -> Vec<(String, String)> {
+        self.spill.entries()
+    }
+
+    fn len(&self) -> usize {
+        self.spill.len()
+    }
+
+    fn clear(&self) {
+        self.spill.clear();
+    }
+
+    fn meta(&self, key: &str) -> Option<EntryMeta> {
+        self.spill.meta(key)
+    }
+
+    fn set_meta(&self, key: String, meta: EntryMeta) {
+        self.spill.set_meta(key, meta);
+    }
+
+    fn insert_with_meta(&self, key: String, value: String, meta: EntryMeta) {
+        self.spill.insert_with_meta(key, value, meta);
+    }
+
+    fn reload(&self) {
+        self.spill.clear();
+        self.spill.import(&self.path);
+    }
+
+    fn flush(&self) {
+        self.spill.read(TABLE, |table| {
+            write_ron(&self.path, &TableMap(table, ToString::to_string));
+        });
+
+        let meta_path = meta_path(&self.path);
+        self.spill.read(META, |meta| {
+            let is_empty = meta.is_empty().expect("failed to count the metadata");
+            if !is_empty || fs::metadata(&meta_path).is_ok() {
+                let parse = |meta: &str| -> EntryMeta {
+                    serde_json::from_str(meta).unwrap_or_else(|e| {
+                        panic!("failed to parse metadata because of error: {e}")
+                    })
+                };
+                write_ron(&meta_path, &TableMap(meta, parse));
+            }
+        });
+    }
+
+    fn vacuum(&mut self) {
+        self.spill.vacuum();
+        self.flush();
+    }
+}
+
+/// A value kept in memory by [`Lru`], with when it was last used.
+#[derive(Debug)]
+struct Hot {
+    value: String,
+    last_used: AtomicU64,
+}
+
+/// Keeps the most recently used values of another backend in memory, up to a budget in bytes.
+///
+/// Every write goes through to the inner backend, so evicted values are read back from it
+/// when they are needed again. This only saves memory over a backend that doesn't keep
+/// every entry in memory itself, like [`RedbFile`].
+///
+/// Values are evicted in batches by one thread at a time, reads only bump an atomic counter.
+/// A value read back from the inner backend is only kept if no write happened meanwhile,
+/// so it never replaces a newer value.
+#[derive(Debug)]
+pub struct Lru {
+    inner: Box<dyn CacheBackend>,
+    hot: DashMap<String, Hot>,
+    /// the size of the keys and values in `hot`,
+    /// only changed while holding the entry, so it never goes below zero
+    used: AtomicUsize,
+    budget: usize,
+    clock: AtomicU64,
+    /// bumped by every write, after it reached the inner backend
+    version: AtomicU64,
+    evictions: AtomicU64,
+    evicting: Mutex<()>,
+}
+
+impl Lru {
+    #[must_use]
+    pub fn new(inner: Box<dyn CacheBackend>, budget: usize) -> Self {
+        Self {
+            inner,
+            hot: DashMap::new(),
+            used: AtomicUsize::new(0),
+            budget,
+            clock: AtomicU64::new(0),
+            version: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            evicting: Mutex::new(()),
+        }
+    }
+
+    /// how many bytes of keys and values are kept in memory
+    #[must_use]
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    /// a counter that goes up on every use, to order the values by
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// mark that a write reached the inner backend, reads started before it keep their value out
+    fn bump_version(&self) {
+        self.version.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn hot(&self, value: String) -> Hot {
+        Hot {
+            value,
+            last_used: AtomicU64::new(self.tick()),
+        }
+    }
+
+    /// Add `size` bytes to what is in memory, whether that is over budget now.
+    fn grow(&self, size: usize) -> bool {
+        self.used.fetch_add(size, Ordering::SeqCst) + size > self.budget
+    }
+
+    /// Keep a value that was just written in memory, replacing what was there,
+    /// and evict the least recently used values if that goes over budget.
+    fn admit(&self, key: String, value: String) {
+        let size = key.len() + value.len();
+        if size > self.budget {
+            self.forget(&key);
+            return;
+        }
+
+        let hot = self.hot(value);
+        let over_budget = match self.hot.entry(key) {
+            Entry::Occupied(mut entry) => {
+                let old = entry.insert(hot);
+                let over_budget = self.grow(size);
+                self.used
+                    .fetch_sub(entry.key().len() + old.value.len(), Ordering::SeqCst);
+                over_budget
+            }
+            Entry::Vacant(entry) => {
+                let _hot = entry.insert(hot);
+                self.grow(size)
+            }
+        };
+
+        if over_budget {
+            self.evict();
+        }
+    }
+
+    /// Keep a value read from the inner backend in memory,
+    /// unless there is one already or something was written since `seen`.
+    fn admit_read(&self, key: &str, value: &str, seen: u64) {
+        if key.len() + value.len() > self.budget {
+            return;
+        }
+
+        let over_budget = match self.hot.entry(key.to_string()) {
+            // the write bumps the version before it takes the entry,
+            // so checking under the entry can't miss one that would overwrite us
+            Entry::Vacant(entry) if self.version.load(Ordering::SeqCst) == seen => {
+                let _hot = entry.insert(self.hot(value.to_string()));
+                self.grow(key.len() + value.len())
+            }
+            _ => false,
+        };
+
+        if over_budget {
+            self.evict();
+        }
+    }
+
+    /// Drop `key` from memory only.
+    fn forget(&self, key: &str) {
+        if let Some((key, hot)) = self.hot.remove(key) {
+            self.used
+                .fetch_sub(key.len() + hot.value.len(), Ordering::SeqCst);
+        }
+    }
+
+    /// Drop everything from memory only.
+    fn forget_all(&self) {
+        self.hot.retain(|key, hot| {
+            self.used
+                .fetch_sub(key.len() + hot.value.len(), Ordering::SeqCst);
+            false
+        });
+    }
+
+    fn evict(&self) {
+        // another thread is making room already
+        let Ok(_evicting) = self.evicting.try_lock() else {
+            return;
+        };
+
+        let mut by_age = self
+            .hot
+            .iter()
+            .map(|hot| (hot.last_used.load(Ordering::Relaxed), hot.key().clone()))
+            .collect::<Vec<_>>();
+        by_age.sort_unstable();
+
+        for (last_used, key) in by_age {
+            if self.used.load(Ordering::SeqCst) <= self.budget {
+                break;
+            }
+
+            // values used since they were sorted stay
+            let evicted = self.hot.remove_if(&key, |_, hot| {
+                hot.last_used.load(Ordering::Relaxed) == last_used
+            });
+            if let Some((key, hot)) = evicted {
+                self.used
+                    .fetch_sub(key.len() + hot.value.len(), Ordering::SeqCst);
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl CacheBackend for Lru {
+    fn get(&self, key: &str) -> Option<String> {
+        if let Some(hot) = self.hot.get(key) {
+            hot.last_used.store(self.tick(), Ordering::Relaxed);
+            return Some(hot.value.clone());
+        }
+
+        let seen = self.version.load(Ordering::SeqCst);
+        let value = self.inner.get(key)?;
+        self.admit_read(key, &value, seen);
+
+        Some(value)
+    }
+
+    fn insert(&self, key: String, value: String) {
+        self.inner.insert(key.clone(), value.clone());
+        self.bump_version();
+        self.admit(key, value);
+    }
+
+    fn remove(&self, key: &str) -> Option<String> {
+        let value = self.inner.remove(key);
+        self.bump_version();
+        self.forget(key);
+        value
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.inner.keys()
+    }
+
+    fn entries(&self) -> Vec<(String, String)> {
+        self.inner.entries()
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn clear(&self) {
+        self.inner.clear();
+        self.bump_version();
+        self.forget_all();
+    }
+
+    fn meta(&self, key: &str) -> Option<EntryMeta> {
+        self.inner.meta(key)
+    }
+
+    fn set_meta(&self, key: String, meta: EntryMeta) {
+        self.inner.set_meta(key, meta);
+    }
+
+    fn insert_with_meta(&self, key: String, value: String, meta: EntryMeta) {
+        self.inner
+            .insert_with_meta(key.clone(), value.clone(), meta);
+        self.bump_version();
+        self.admit(key, value);
+    }
+
+    fn reload(&self) {
+        self.inner.reload();
+        self.bump_version();
+        self.forget_all();
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+
+    fn vacuum(&mut self) {
+        self.inner.vacuum();
+    }
+
+    fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::test_utils::TempDir;
+    use crate::zaphkiel::cache::{Cache, FlushPolicy};
+
+    const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+    fn open(path: &Path) -> Cache {
+        Cache::new_with_path(path.to_path_buf()).with_flush_policy(FlushPolicy::manual())
+    }
+
+    fn cache(version: &str) -> HashMap<String, String> {
+        HashMap::from([("key".to_string(), version.to_string())])
+    }
+
+    #[test]
+    fn load_ron_falls_back_to_the_backup() {
+        let dir = TempDir::new("recovery");
+        let path = dir.join("cache.ron");
+
+        // overwriting keeps the previous version as the backup
+        write_ron(&path, &cache("old"));
+        write_ron(&path, &cache("new"));
+        assert_eq!(load_ron::<HashMap<String, String>>(&path), cache("new"));
+
+        // a corrupt main file falls back to the backup
+        fs::write(&path, "{\"key\": \"torn").unwrap();
+        assert_eq!(load_ron::<HashMap<String, String>>(&path), cache("old"));
+
+        // so does a missing one
+        fs::remove_file(&path).unwrap();
+        assert_eq!(load_ron::<HashMap<String, String>>(&path), cache("old"));
+    }
+
+    #[test]
+    fn load_ron_starts_empty_without_a_usable_copy() {
+        let dir = TempDir::new("recovery-empty");
+        let path = dir.join("cache.ron");
+
+        fs::write(&path, "not ron").unwrap();
+        fs::write(backup_path(&path), "not ron either").unwrap();
+        assert!(load_ron::<HashMap<String, String>>(&path).is_empty());
+    }
+
+    #[test]
+    fn migrate_compresses_entries_and_metadata() {
+        let dir = TempDir::new("migrate");
+        let path = dir.join("cache.ron");
+
+        let cache = open(&path);
+        let meta = EntryMeta {
+            etag: Some("\"v1\"".to_string()),
+            ..EntryMeta::now()
+        };
+        cache.add_with_meta("key".to_string(), "value ".repeat(1000), meta.clone());
+        cache.dump();
+        drop(cache);
+
+        let (new_path, old_size, new_size) = migrate(&path, CacheFormat::RonZstd).unwrap();
+        assert_eq!(new_path, dir.join("cache.ron.zst"));
+        assert!(new_size < old_size);
+        assert!(!path.exists() && !dir.join("cache.meta.ron").exists());
+
+        assert_eq!(fs::read(&new_path).unwrap()[..4], ZSTD_MAGIC);
+        let cache = open(&new_path);
+        assert_eq!(cache.get("key"), Some("value ".repeat(1000)));
+        assert_eq!(cache.get_meta("key"), Some(meta));
+
+        // and stays compressed when written to
+        cache.add("other".to_string(), "value".to_string());
+        cache.dump();
+        assert_eq!(fs::read(&new_path).unwrap()[..4], ZSTD_MAGIC);
+        assert_eq!(open(&new_path).get("other"), Some("value".to_string()));
+
+        // already compressed, or not a cache at all
+        assert_eq!(migrate(&new_path, CacheFormat::RonZstd), None);
+        fs::write(dir.join("last_page.ron"), "5").unwrap();
+        assert_eq!(
+            migrate(&dir.join("last_page.ron"), CacheFormat::RonZstd),
+            None
+        );
+    }
+
+    #[test]
+    fn redb_imports_ron_caches_with_their_metadata() {
+        for format in [CacheFormat::Ron, CacheFormat::RonZstd] {
+            let dir = TempDir::new("redb-import-meta");
+            let meta = EntryMeta {
+                status: Some(200),
+                etag: Some("\"v1\"".to_string()),
+                last_modified: Some("Mon, 01 Jan 2024 00:00:00 GMT".to_string()),
+                ..EntryMeta::now()
+            };
+            let ron = open(&dir.join(format!("cache.{}", format.extension())));
+            ron.add_with_meta("key".to_string(), "value".to_string(), meta.clone());
+            ron.add("other".to_string(), "value".to_string());
+            ron.dump();
+            drop(ron);
+
+            let redb = open(&dir.join("cache.redb"));
+            assert_eq!(redb.get("key"), Some("value".to_string()), "{format:?}");
+            assert_eq!(redb.get_meta("key"), Some(meta), "{format:?}");
+            assert_eq!(redb.get_stats().cache_size, 2, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn cache_files_are_told_apart_by_their_header() {
+        let dir = TempDir::new("is-cache-file");
+        for format in [CacheFormat::Ron, CacheFormat::RonZstd, CacheFormat::Redb] {
+            let cache = open(&dir.join(format!("cache.{}", format.extension())));
+            cache.add("key".to_string(), "value".to_string());
+            cache.dump();
+        }
+        fs::write(dir.join("last_page.ron"), "5").unwrap();
+        fs::write(dir.join("notes.redb"), "not a database").unwrap();
+        fs::write(dir.join("empty.ron.zst"), "").unwrap();
+
+        assert!(is_cache_file(&dir.join("cache.ron")));
+        assert!(is_cache_file(&dir.join("cache.ron.zst")));
+        assert!(is_cache_file(&dir.join("cache.redb")));
+        assert!(!is_cache_file(&dir.join("cache.meta.ron")));
+        assert!(!is_cache_file(&dir.join("last_page.ron")));
+        assert!(!is_cache_file(&dir.join("notes.redb")));
+        assert!(!is_cache_file(&dir.join("empty.ron.zst")));
+        assert!(!is_cache_file(&dir.join("missing.ron")));
+
+        let redb = RedbFile::open(dir.join("cache.redb"));
+        assert_eq!(redb.keys(), vec!["key".to_string()]);
+    }
+
+    #[test]
+    fn redb_starts_empty_from_a_corrupt_ron_cache() {
+        let dir = TempDir::new("redb-import");
+        fs::write(dir.join("cache.ron"), "{\"key\": \"torn").unwrap();
+
+        assert!(RedbFile::open(dir.join("cache.redb")).is_empty());
+    }
+
+    #[test]
+    fn lru_never_keeps_a_value_older_than_the_last_write() {
+        let lru = Lru::new(Box::<RonFile>::default(), 64);
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 0..2000 {
+                    lru.insert(format!("key-{}", i % 4), i.to_string());
+                    if i % 7 == 0 {
+                        lru.remove(&format!("key-{}", i % 4));
+                    }
+                }
+            });
+            for _ in 0..3 {
+                scope.spawn(|| {
+                    for i in 0..2000 {
+                        lru.get(&format!("key-{}", i % 4));
+                    }
+                });
+            }
+        });
+
+        assert_eq!(lru.get("key-3"), Some("1999".to_string()));
+        assert_eq!(lru.get("key-0"), Some("1996".to_string()));
+        assert!(lru.used() <= 64);
+        lru.clear();
+        assert_eq!(lru.used(), 0);
+    }
+}
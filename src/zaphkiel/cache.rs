@@ -1,26 +1,96 @@
+use std::borrow::Borrow;
 use std::collections::HashMap;
-use std::fs;
-use std::fs::File;
-use std::path::PathBuf;
-use std::sync::Arc;
-use std::sync::RwLock;
-
-use path_absolutize::Absolutize;
-use ron::ser::{to_string_pretty, PrettyConfig};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
 use serde::{Deserialize, Serialize};
 
-use crate::time_it;
+use crate::zaphkiel::backend::{
+    load_ron, meta_path, read_ron, write_ron, CacheBackend, CacheFormat, RonFile,
+};
+use crate::zaphkiel::codec::{Decode, Encode};
 
 /// generic cache that stores a key-value pair
-/// It is safe to use this cache from multiple threads if you wrap it in an Arc<`RwLock`<_>>
-#[derive(Debug, Default, Clone)]
-pub struct Cache {
-    cache: HashMap<String, String>,
-    stats: Arc<RwLock<CacheStats>>,
-    misses: Arc<RwLock<Vec<String>>>,
-    hits: Arc<RwLock<Vec<String>>>,
-    accesses: u64,
+/// Keys and values are strings unless other [`Encode`]/[`Decode`] types are given,
+/// e.g. [`Json`](crate::zaphkiel::codec::Json) for serde types.
+/// It is safe to share this cache between threads in an Arc<_>, every method takes `&self`.
+/// The stats are atomic counters and the ron backend keeps entries in a sharded map,
+/// so callers don't need a lock around the whole cache. A write that reaches the flush
+/// threshold while another flush is running doesn't wait for it, its change goes into the next one.
+/// Writes to the same shard still wait on each other, and redb runs one write transaction at a time.
+///
+/// `benches/cache.rs` on one core, looking up 10 000 keys and adding the missing half:
+/// 1.25 s for the old locked cache, 1.34 s with ron and 0.99 s with redb,
+/// rewriting the whole ron file on every flush dominates. With every key cached:
+/// 2.2 ms locked, 2.8 ms with ron and 17 ms with redb, which reads every value from disk.
+///
+/// Changes are written to disk according to its [`FlushPolicy`].
+///
+/// # Example
+/// ```
+/// use booth_archiver::zaphkiel::cache::Cache;
+///
+/// let cache: Cache = Cache::default();
+/// cache.add("key".to_string(), "value".to_string());
+/// assert_eq!(cache.get("key"), Some("value".to_string()));
+/// ```
+#[derive(Debug)]
+pub struct Cache<K = String, V = String> {
+    backend: Box<dyn CacheBackend>,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    /// keys which caused misses, appending doesn't block readers or other writers
+    misses: boxcar::Vec<String>,
+    hits: boxcar::Vec<String>,
+    accesses: AtomicU64,
     path_to_cache: PathBuf,
+    /// how long entries stay fresh, `None` keeps them forever
+    ttl: Option<Duration>,
+    flush_policy: FlushPolicy,
+    /// whether there are changes that were not flushed yet
+    dirty: AtomicBool,
+    writes_since_flush: AtomicU64,
+    last_flush: Mutex<Instant>,
+    /// held while flushing, so two threads never write the cache file at once
+    flush_lock: Mutex<()>,
+    types: PhantomData<fn() -> (K, V)>,
+}
+
+/// When a [`Cache`] writes its changes to disk, besides explicit calls to `dump`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlushPolicy {
+    /// flush after this many writes
+    pub every_writes: Option<u64>,
+    /// flush on a write when this long has passed since the last flush
+    pub every: Option<Duration>,
+    /// flush when the cache is dropped
+    pub on_drop: bool,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        Self {
+            every_writes: Some(100),
+            every: None,
+            on_drop: true,
+        }
+    }
+}
+
+impl FlushPolicy {
+    /// only flush on explicit calls to `dump`
+    #[must_use]
+    pub const fn manual() -> Self {
+        Self {
+            every_writes: None,
+            every: None,
+            on_drop: false,
+        }
+    }
 }
 
 /// Stats for the cache
@@ -30,173 +100,380 @@ pub struct CacheStats {
     pub cache_hits: u64,
     pub cache_misses: u64,
     pub cache_size: usize,
+    /// values dropped from memory to stay within the memory budget, they are still on disk
+    #[serde(default)]
+    pub evictions: u64,
 }
 
-impl Cache {
-    /// Create a new cache with a custom path
+/// When and how a cached value was fetched
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct EntryMeta {
+    /// unix timestamp in seconds
+    pub fetched_at: u64,
+    /// the http status of the response, if the value came from one
+    pub status: Option<u16>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl EntryMeta {
+    /// Metadata for a value fetched right now
+    #[must_use]
+    pub fn now() -> Self {
+        Self {
+            fetched_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+            ..Self::default()
+        }
+    }
+
+    /// how long ago the value was fetched
+    #[must_use]
+    pub fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH + Duration::from_secs(self.fetched_at))
+            .unwrap_or_default()
+    }
+}
+
+impl<K, V> Default for Cache<K, V> {
+    /// an in memory cache that is never written to disk
+    fn default() -> Self {
+        Self::with_backend(Box::<RonFile>::default(), PathBuf::new())
+            .with_flush_policy(FlushPolicy::manual())
+    }
+}
+
+impl<K, V> Drop for Cache<K, V> {
+    fn drop(&mut self) {
+        // flushing can panic, which would abort if we are already unwinding
+        if self.flush_policy.on_drop && !thread::panicking() {
+            self.dump();
+        }
+    }
+}
+
+impl<K, V> Cache<K, V> {
+    /// Create a new cache with a custom path, the format is picked by the extension
     pub fn new_with_path(path_to_cache: PathBuf) -> Self {
-        let cache = match fs::metadata(&path_to_cache) {
-            Ok(_) => {
-                let abs_path = path_to_cache
-                    .absolutize()
-                    .expect("failed to absolutize path");
-                let abs_path = abs_path.to_str().expect("failed to convert path to str");
-
-                let cache = fs::read_to_string(&path_to_cache)
-                    .unwrap_or_else(|_| panic!("{} not found", abs_path));
-
-                let cache: HashMap<String, String> = ron::from_str(&cache).unwrap_or_else(|e| {
-                    panic!(
-                        "Failed to parse {}, {} exists but the ron data is invalid\n\
-                        Failed with error: {}",
-                        abs_path, abs_path, e
-                    )
-                });
+        Self::new_with_budget(path_to_cache, None)
+    }
 
-                cache
-            }
-            Err(_) => HashMap::new(),
-        };
+    /// Create a new cache with a custom path that keeps at most `budget` bytes in memory,
+    /// the least recently used values are read back from disk when they are needed again,
+    /// see [`CacheFormat::open_with_budget`].
+    pub fn new_with_budget(path_to_cache: PathBuf, budget: Option<usize>) -> Self {
+        let backend =
+            CacheFormat::from_path(&path_to_cache).open_with_budget(path_to_cache.clone(), budget);
 
+        Self::with_backend(backend, path_to_cache)
+    }
+
+    /// Create a new cache stored in the given backend
+    pub fn with_backend(backend: Box<dyn CacheBackend>, path_to_cache: PathBuf) -> Self {
         Self {
-            cache,
+            backend,
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+            misses: boxcar::Vec::new(),
+            hits: boxcar::Vec::new(),
+            accesses: AtomicU64::new(0),
             path_to_cache,
-            ..Self::default()
+            ttl: None,
+            flush_policy: FlushPolicy::default(),
+            dirty: AtomicBool::new(false),
+            writes_since_flush: AtomicU64::new(0),
+            last_flush: Mutex::new(Instant::now()),
+            flush_lock: Mutex::new(()),
+            types: PhantomData,
         }
     }
+
+    /// Set when changes are written to disk
+    #[must_use]
+    pub const fn with_flush_policy(mut self, flush_policy: FlushPolicy) -> Self {
+        self.flush_policy = flush_policy;
+        self
+    }
+
+    /// Set how long entries stay fresh, expired entries count as misses
+    #[must_use]
+    pub const fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
 }
 
-impl Cache {
+impl<K: Encode, V: Encode + Decode> Cache<K, V> {
     #[inline]
     /// Add a key-value pair to the cache
-    pub fn add(&mut self, key: String, value: String) {
-        self.cache.insert(key, value);
-        self.accesses += 1;
-        if self.accesses % 100 == 0 {
-            self.dump();
-        }
+    pub fn add(&self, key: K, value: V) {
+        self.add_with_meta(key, value, EntryMeta::now());
     }
 
     #[inline]
-    /// Get a value from the cache
+    /// Add a key-value pair to the cache, along with how it was fetched
+    pub fn add_with_meta(&self, key: K, value: V, meta: EntryMeta) {
+        self.backend
+            .insert_with_meta(key.encode().into_owned(), value.encode().into_owned(), meta);
+        self.accesses.fetch_add(1, Ordering::Relaxed);
+        self.mark_dirty();
+    }
+
+    #[inline]
+    /// Get a value from the cache, if it has not expired
     #[must_use]
-    pub fn get(&self, key: &String) -> Option<String> {
-        self.cache.get(key).map_or_else(
+    pub fn get<Q: Encode + ?Sized>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        let key = key.encode();
+        let value = self
+            .backend
+            .get(&key)
+            .filter(|_| self.is_fresh_encoded(&key))
+            .and_then(|value| V::decode_owned(value).ok());
+
+        value.map_or_else(
             || {
-                self.miss();
-                self.misses.write().unwrap().push(key.clone());
+                self.record_miss_encoded(&key);
                 None
             },
             |value| {
-                self.hit();
-                self.hits.write().unwrap().push(key.clone());
-                Some(value.clone())
+                self.record_hit_encoded(&key);
+                Some(value)
             },
         )
     }
+
+    #[inline]
+    /// Get a value from the cache even if it has expired, along with how it was fetched,
+    /// without counting it as a hit or a miss
+    #[must_use]
+    pub fn peek<Q: Encode + ?Sized>(&self, key: &Q) -> Option<(V, Option<EntryMeta>)>
+    where
+        K: Borrow<Q>,
+    {
+        let key = key.encode();
+
+        self.backend
+            .get(&key)
+            .and_then(|value| V::decode_owned(value).ok())
+            .map(|value| (value, self.backend.meta(&key)))
+    }
+
+    /// Remove an entry and its metadata, returning its value if there was one
+    pub fn remove<Q: Encode + ?Sized>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        let value = self.backend.remove(&key.encode())?;
+        self.mark_dirty();
+
+        V::decode_owned(value).ok()
+    }
+
+    /// Mark an expired entry as fresh again because its source did not change,
+    /// counting it as a hit
+    pub fn revalidate<Q: Encode + ?Sized>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+    {
+        let key = key.encode();
+        let meta = self.backend.meta(&key).unwrap_or_default();
+        self.backend.set_meta(
+            key.to_string(),
+            EntryMeta {
+                fetched_at: EntryMeta::now().fetched_at,
+                ..meta
+            },
+        );
+        self.record_hit_encoded(&key);
+        self.mark_dirty();
+    }
+
+    /// Get how the value of `key` was fetched, entries cached before this was recorded have none
+    #[must_use]
+    pub fn get_meta<Q: Encode + ?Sized>(&self, key: &Q) -> Option<EntryMeta>
+    where
+        K: Borrow<Q>,
+    {
+        self.backend.meta(&key.encode())
+    }
+
+    /// whether the entry is younger than the ttl, entries of unknown age only are without one
+    #[must_use]
+    pub fn is_fresh<Q: Encode + ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.is_fresh_encoded(&key.encode())
+    }
+
+    #[inline]
+    /// counts a hit on `key`
+    pub fn record_hit<Q: Encode + ?Sized>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+    {
+        self.record_hit_encoded(&key.encode());
+    }
+
+    #[inline]
+    /// counts a miss on `key`
+    pub fn record_miss<Q: Encode + ?Sized>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+    {
+        self.record_miss_encoded(&key.encode());
+    }
 }
 
-impl Cache {
+impl<K, V> Cache<K, V> {
+    fn is_fresh_encoded(&self, key: &str) -> bool {
+        self.ttl
+            .is_none_or(|ttl| self.backend.meta(key).is_some_and(|meta| meta.age() <= ttl))
+    }
+
+    fn record_hit_encoded(&self, key: &str) {
+        self.hit();
+        self.hits.push(key.to_string());
+    }
+
+    fn record_miss_encoded(&self, key: &str) {
+        self.miss();
+        self.misses.push(key.to_string());
+    }
+
+    /// Remember that there are unsaved changes, and flush if the policy says so
+    fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::SeqCst);
+        let writes = self.writes_since_flush.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let FlushPolicy {
+            every_writes,
+            every,
+            on_drop: _,
+        } = self.flush_policy;
+        let enough_writes = every_writes.is_some_and(|every_writes| writes >= every_writes);
+        let long_enough =
+            every.is_some_and(|every| self.last_flush.lock().unwrap().elapsed() >= every);
+
+        // writers don't queue up behind a flush that is running already,
+        // their changes are left for the next one
+        if enough_writes || long_enough {
+            if let Ok(flushing) = self.flush_lock.try_lock() {
+                self.flush(flushing);
+            }
+        }
+    }
+
+    /// whether there are changes that were not written to disk yet
+    #[must_use]
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::SeqCst)
+    }
+}
+
+impl<K, V> Cache<K, V> {
     #[inline]
     /// increments the cache hit counter
     pub fn hit(&self) {
-        self.stats.clone().write().unwrap().cache_hits += 1;
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
     }
 
     #[inline]
     /// increments the cache miss counter
     pub fn miss(&self) {
-        self.stats.clone().write().unwrap().cache_misses += 1;
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
     }
 }
 
-impl Cache {
-    /// pump the cache from the cache file
-    pub fn pump(&mut self) {
-        self.pump_from_file(&self.path_to_cache.clone());
+impl<K, V> Cache<K, V> {
+    /// pump the cache from the cache file, dropping unsaved changes
+    pub fn pump(&self) {
+        self.backend.reload();
+        self.dirty.store(false, Ordering::SeqCst);
+        self.writes_since_flush.store(0, Ordering::SeqCst);
     }
 
-    /// dump the cache to the cache file
+    /// dump the cache to the cache file, if anything changed since the last dump
     pub fn dump(&self) {
-        self.dump_to_file(&self.path_to_cache);
-    }
-
-    /// pump the cache from the given cache file
-    pub fn pump_from_file(&mut self, cache_location: &PathBuf) {
-        let abs_path = cache_location
-            .absolutize()
-            .expect("failed to absolutize path");
-        let abs_path = abs_path.to_str().expect("failed to convert path to str");
-        let cache = time_it!("reading from cache file" =>
-        fs::read_to_string(cache_location)
-            .unwrap_or_else(|_| panic!("{} not found", abs_path))
-        );
-
-        let cache: HashMap<String, String> = time_it!("converting to hashmap from string" =>
-            ron::from_str(&cache)
-                .unwrap_or_else(|e| panic!("Failed to parse {}, \
-                {} exists but the ron data is invalid\n\
-                failed with error: {}", abs_path, abs_path, e))
-        );
-
-        self.cache = cache;
+        self.flush(self.flush_lock.lock().unwrap());
     }
 
-    /// dump the cache to the given cache file
-    pub fn dump_to_file(&self, cache_location: &PathBuf) {
-        if self.get_stats().cache_misses == 0 {
+    /// write the changes to disk, while holding the flush lock
+    fn flush(&self, _flushing: MutexGuard<'_, ()>) {
+        if !self.dirty.swap(false, Ordering::SeqCst) {
             return;
         }
+        // writes during the flush count towards the next one
+        self.writes_since_flush.store(0, Ordering::SeqCst);
 
-        let cache = to_string_pretty(&self.cache, PrettyConfig::default())
-            .expect("failed to serialize from hashmap to ron");
-
-        let path = cache_location.to_str().unwrap_or_else(|| {
-            panic!(
-                "{} not found",
-                cache_location
-                    .absolutize()
-                    .expect("failed to absolutize path")
-                    .to_str()
-                    .expect("failed to convert path to str")
-            )
-        });
+        self.backend.flush();
+        *self.last_flush.lock().unwrap() = Instant::now();
+    }
+
+    /// replace the cache with the given ron file and the metadata next to it,
+    /// entries without metadata count as never fetched
+    pub fn pump_from_file(&self, cache_location: &Path) {
+        let cache = read_ron(cache_location);
+        let mut meta: HashMap<String, EntryMeta> = load_ron(&meta_path(cache_location));
 
-        if fs::metadata(path).is_err() {
-            File::create(path).unwrap_or_else(|err| {
-                panic!("Failed to create file `{path}` due to error: {err}`")
-            });
+        self.backend.clear();
+        for (key, value) in cache {
+            match meta.remove(&key) {
+                Some(meta) => self.backend.insert_with_meta(key, value, meta),
+                None => self.backend.insert(key, value),
+            }
         }
+        self.mark_dirty();
+    }
+
+    /// dump the cache to the given ron file, and its metadata next to it,
+    /// e.g. `export.meta.ron` for `export.ron`
+    pub fn dump_to_file(&self, cache_location: &Path) {
+        let entries = self.backend.entries();
+        let meta = entries
+            .iter()
+            .filter_map(|(key, _)| Some((key.clone(), self.backend.meta(key)?)))
+            .collect::<HashMap<_, _>>();
+        let cache = entries.into_iter().collect::<HashMap<_, _>>();
 
-        fs::write(path, cache)
-            .unwrap_or_else(|e| panic!("failed to write to cache file because of error: {}", e));
+        write_ron(cache_location, &cache);
+        write_ron(&meta_path(cache_location), &meta);
     }
 }
 
-impl Cache {
+impl<K, V> Cache<K, V> {
     /// get the stats of the cache
     #[must_use]
     pub fn get_stats(&self) -> CacheStats {
-        self.stats.write().unwrap().cache_size = self.cache.len();
-        self.stats.clone().read().unwrap().clone()
+        CacheStats {
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
+            cache_size: self.backend.len(),
+            evictions: self.backend.evictions(),
+        }
     }
 
     /// get the keys which caused misses
     #[must_use]
     pub fn get_misses(&self) -> Vec<String> {
-        self.misses.clone().read().unwrap().clone()
+        self.misses.iter().map(|(_, key)| key.clone()).collect()
     }
 
     /// get the keys which caused hits
     #[must_use]
     pub fn get_hits(&self) -> Vec<String> {
-        self.hits.clone().read().unwrap().clone()
+        self.hits.iter().map(|(_, key)| key.clone()).collect()
     }
 
     /// get the number of times the cache was accessed
     #[must_use]
-    pub const fn get_accesses(&self) -> u64 {
-        self.accesses
+    pub fn get_accesses(&self) -> u64 {
+        self.accesses.load(Ordering::Relaxed)
     }
 
     /// get the path to the cache file
@@ -205,16 +482,179 @@ impl Cache {
         self.path_to_cache.clone()
     }
 
-    pub fn keys(&self) -> impl Iterator<Item = String> + '_ {
-        self.cache.keys().cloned()
+    /// the keys in the cache, skipping those that are not a valid `K`
+    pub fn keys(&self) -> impl Iterator<Item = K> + '_
+    where
+        K: Decode,
+    {
+        self.backend
+            .keys()
+            .into_iter()
+            .filter_map(|key| K::decode(&key).ok())
     }
 
     /// clear the cache
-    pub fn clear(&mut self) {
-        self.cache.clear();
+    pub fn clear(&self) {
+        self.backend.clear();
+        self.mark_dirty();
+    }
+
+    /// drop the metadata of removed entries and shrink the cache file, writing it right away
+    pub fn vacuum(&mut self) {
+        self.backend.vacuum();
+        self.dirty.store(false, Ordering::SeqCst);
+        self.writes_since_flush.store(0, Ordering::SeqCst);
+        *self.last_flush.lock().unwrap() = Instant::now();
     }
 
     pub fn capacity(&self) -> usize {
-        self.cache.capacity()
+        self.backend.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_utils::TempDir;
+    use crate::zaphkiel::backend::spill_path;
+
+    fn open(path: &Path) -> Cache {
+        Cache::new_with_path(path.to_path_buf()).with_flush_policy(FlushPolicy::manual())
+    }
+
+    #[test]
+    fn concurrent_adds_are_saved_on_drop() {
+        let dir = TempDir::new("concurrent");
+        let path = dir.join("cache.ron");
+        let cache: Arc<Cache> = Arc::new(Cache::new_with_path(path.clone()));
+
+        thread::scope(|scope| {
+            for thread in 0..4 {
+                let cache = &cache;
+                scope.spawn(move || {
+                    for i in 0..250 {
+                        cache.add(format!("{thread}-{i}"), i.to_string());
+                    }
+                });
+            }
+        });
+        drop(cache);
+
+        let cache = open(&path);
+        assert_eq!(cache.get_stats().cache_size, 1000);
+        assert_eq!(cache.get("3-249"), Some("249".to_string()));
+    }
+
+    #[test]
+    fn memory_budget_evicts_the_least_recently_used() {
+        let dir = TempDir::new("budget");
+        let path = dir.join("cache.ron");
+        // half of it is redb's page cache
+        let cache: Cache = Cache::new_with_budget(path.clone(), Some(128))
+            .with_flush_policy(FlushPolicy::manual());
+
+        for i in 0..10 {
+            cache.add(format!("key-{i}"), "x".repeat(20));
+        }
+
+        let stats = cache.get_stats();
+        assert_eq!(stats.cache_size, 10);
+        assert_eq!(stats.evictions, 8);
+        assert_eq!(cache.get("key-0"), Some("x".repeat(20)));
+
+        // the evicted values are still written to the ron file, with their metadata
+        let meta = EntryMeta {
+            etag: Some("\"v1\"".to_string()),
+            ..EntryMeta::now()
+        };
+        cache.add_with_meta("key-0".to_string(), "y".repeat(20), meta.clone());
+        cache.dump();
+        drop(cache);
+        assert!(!spill_path(&path).exists());
+
+        let cache = open(&path);
+        assert_eq!(cache.get_stats().cache_size, 10);
+        assert_eq!(cache.get("key-9"), Some("x".repeat(20)));
+        assert_eq!(cache.get("key-0"), Some("y".repeat(20)));
+        assert_eq!(cache.get_meta("key-0"), Some(meta));
+    }
+
+    #[test]
+    fn memory_budget_on_redb_bounds_the_page_cache() {
+        let dir = TempDir::new("budget-redb");
+        let cache: Cache = Cache::new_with_budget(dir.join("cache.redb"), Some(64));
+
+        for i in 0..10 {
+            cache.add(format!("key-{i}"), "x".repeat(20));
+        }
+        assert_eq!(cache.get("key-0"), Some("x".repeat(20)));
+        assert_eq!(cache.get_stats().evictions, 0);
+    }
+
+    #[test]
+    fn entries_added_after_only_hits_are_saved() {
+        let dir = TempDir::new("hit-only");
+        let path = dir.join("cache.ron");
+
+        let cache = open(&path);
+        cache.add("a".to_string(), "1".to_string());
+        cache.dump();
+
+        let cache = open(&path);
+        assert_eq!(cache.get("a"), Some("1".to_string()));
+        assert!(!cache.is_dirty());
+        cache.add("b".to_string(), "2".to_string());
+        assert!(cache.is_dirty());
+        cache.dump();
+        assert!(!cache.is_dirty());
+
+        assert_eq!(open(&path).get("b"), Some("2".to_string()));
+    }
+
+    #[test]
+    fn clear_then_add_saves_only_the_new_entries() {
+        let dir = TempDir::new("clear");
+        let path = dir.join("cache.ron");
+
+        let cache = open(&path);
+        cache.add("a".to_string(), "1".to_string());
+        cache.dump();
+
+        let cache = open(&path);
+        cache.clear();
+        cache.add("b".to_string(), "2".to_string());
+        cache.dump();
+
+        assert_eq!(open(&path).keys().collect::<Vec<_>>(), ["b"]);
+    }
+
+    #[test]
+    fn export_and_import_keep_the_metadata() {
+        let dir = TempDir::new("export");
+        let export = dir.join("export.ron");
+        let ttl = Some(Duration::from_secs(60));
+
+        let cache = open(&dir.join("a.ron")).with_ttl(ttl);
+        let meta = EntryMeta {
+            etag: Some("\"v1\"".to_string()),
+            ..EntryMeta::now()
+        };
+        cache.add_with_meta("page".to_string(), "<html>".to_string(), meta.clone());
+        cache.dump_to_file(&export);
+        assert!(dir.join("export.meta.ron").exists());
+
+        // importing replaces what was in the cache
+        let other = open(&dir.join("b.ron")).with_ttl(ttl);
+        other.add("stale".to_string(), "gone".to_string());
+        other.pump_from_file(&export);
+        assert_eq!(other.get("stale"), None);
+        assert_eq!(other.get("page"), Some("<html>".to_string()));
+        assert_eq!(other.get_meta("page"), Some(meta));
+        assert!(other.is_fresh("page"));
     }
 }
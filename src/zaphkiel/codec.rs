@@ -0,0 +1,115 @@
+use std::borrow::Cow;
+use std::ops::{Deref, DerefMut};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// How a key or value of a [`Cache`](crate::zaphkiel::cache::Cache) is written to its backend.
+pub trait Encode {
+    fn encode(&self) -> Cow<'_, str>;
+}
+
+/// How a key or value of a [`Cache`](crate::zaphkiel::cache::Cache) is read from its backend.
+pub trait Decode: Sized {
+    /// # Errors
+    /// if `text` is not a valid encoding of `Self`
+    fn decode(text: &str) -> Result<Self, String>;
+
+    /// like [`Decode::decode`], for types that can reuse the backend's string
+    ///
+    /// # Errors
+    /// if `text` is not a valid encoding of `Self`
+    fn decode_owned(text: String) -> Result<Self, String> {
+        Self::decode(&text)
+    }
+}
+
+/// strings are stored as is, e.g. raw response bodies
+impl Encode for str {
+    fn encode(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl Encode for String {
+    fn encode(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl Decode for String {
+    fn decode(text: &str) -> Result<Self, String> {
+        Ok(text.to_string())
+    }
+
+    fn decode_owned(text: String) -> Result<Self, String> {
+        Ok(text)
+    }
+}
+
+/// Stores any serde type in a cache as json, so it is parsed once when read.
+///
+/// # Example
+/// ```
+/// use booth_archiver::zaphkiel::cache::Cache;
+/// use booth_archiver::zaphkiel::codec::Json;
+///
+/// let prices: Cache<String, Json<(f64, String)>> = Cache::default();
+/// prices.add("item".to_string(), Json((500.0, "JPY".to_string())));
+/// assert_eq!(prices.get("item"), Some(Json((500.0, "JPY".to_string()))));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> Encode for Json<T> {
+    fn encode(&self) -> Cow<'_, str> {
+        Cow::Owned(
+            serde_json::to_string(&self.0)
+                .unwrap_or_else(|e| panic!("failed to serialize to json because of error: {e}")),
+        )
+    }
+}
+
+impl<T: DeserializeOwned> Decode for Json<T> {
+    fn decode(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text)
+            .map(Json)
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+    use crate::zaphkiel::cache::{Cache, FlushPolicy};
+
+    #[test]
+    fn json_values_survive_a_dump() {
+        let dir = TempDir::new("typed");
+        let path = dir.join("prices.ron");
+
+        let prices: Cache<String, Json<(f64, String)>> =
+            Cache::new_with_path(path.clone()).with_flush_policy(FlushPolicy::manual());
+        prices.add("item".to_string(), Json((500.0, "JPY".to_string())));
+        prices.dump();
+
+        let prices: Cache<String, Json<(f64, String)>> = Cache::new_with_path(path);
+        assert_eq!(prices.get("item"), Some(Json((500.0, "JPY".to_string()))));
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use indicatif::{ProgressBar, ProgressStyle};
 
 #[must_use]
@@ -16,6 +18,73 @@ pub fn get_pb(len: u64, msg: &'static str) -> ProgressBar {
 
     pb
 }
+
+/// Parse a duration like `90`, `90s`, `30m`, `12h`, `7d` or `2w`, plain numbers are seconds.
+#[must_use]
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number = number.parse::<u64>().ok()?;
+
+    let secs = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+
+    Some(Duration::from_secs(number.checked_mul(secs)?))
+}
+
+/// Whether `text` matches the glob `pattern`, where `*` matches any run of characters
+/// and `?` matches one.
+///
+/// # Example
+/// ```
+/// use booth_archiver::zaphkiel::utils::glob_match;
+///
+/// assert!(glob_match("https://booth.pm/*/items/*.json", "https://booth.pm/en/items/1.json"));
+/// assert!(glob_match("item-?", "item-1"));
+/// assert!(!glob_match("item-?", "item-10"));
+/// assert!(glob_match("exact", "exact"));
+/// ```
+#[must_use]
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    let (mut p, mut t) = (0, 0);
+    // where the last `*` was and how much of the text it swallowed
+    let mut star = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
 //
 // pub fn unneeded_values(all_items: &[ItemApiResponse]) {
 //     // pub factory_description: Option<Value>,